@@ -0,0 +1,334 @@
+//! Leaderboard server
+//!
+//! Small HTTP server for the Rusty Hitman global leaderboard. Scores are kept in
+//! memory and saved to a local JSON file after every submission.
+//!
+//! Usage: `leaderboard-server [address] [file]`, defaults to `127.0.0.1:8080` and
+//! `leaderboard.json`.
+//!
+//! Endpoints:
+//! - `POST /scores` with a score entry as the body, responds with its rank.
+//! - `GET /scores?difficulty=..&mode=..&count=..` responds with the top scores.
+//! - `GET /rank?difficulty=..&mode=..&score=..` responds with the rank of the score.
+
+use nanoserde::{DeJson, SerJson};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+const DEFAULT_FILE: &str = "leaderboard.json";
+/// Longest player name accepted.
+const MAX_NAME_LEN: usize = 16;
+/// Most scores returned by a single request.
+const MAX_COUNT: usize = 100;
+/// Largest request body accepted, in bytes.
+const MAX_BODY_LEN: usize = 4096;
+/// Largest request line and headers accepted, in bytes.
+const MAX_HEAD_LEN: usize = 8192;
+/// Time a client has to send its whole request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A score on the leaderboard.
+#[derive(Clone, Debug, SerJson, DeJson)]
+struct ScoreEntry {
+    name: String,
+    score: f32,
+    level: f32,
+    difficulty: String,
+    mode: String,
+}
+
+/// The leaderboard file.
+#[derive(Default, SerJson, DeJson)]
+struct Scores {
+    scores: Vec<ScoreEntry>,
+}
+
+#[derive(SerJson)]
+struct RankResponse {
+    rank: usize,
+}
+
+#[derive(SerJson)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// A parsed HTTP request.
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    body: String,
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let file = args.next().unwrap_or_else(|| DEFAULT_FILE.to_string());
+
+    let mut scores = match fs::read_to_string(&file) {
+        Ok(json) => Scores::deserialize_json(&json).unwrap_or_else(|e| {
+            eprintln!("Invalid leaderboard file {}: {}", file, e);
+            std::process::exit(1);
+        }),
+        Err(_) => Scores::default(), // No scores yet
+    };
+
+    let listener = TcpListener::bind(&address).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on {}: {}", address, e);
+        std::process::exit(1);
+    });
+    println!(
+        "Leaderboard listening on http://{} ({} scores in {})",
+        address,
+        scores.scores.len(),
+        file
+    );
+
+    for stream in listener.incoming().flatten() {
+        if let Err(e) = handle(stream, &mut scores, &file) {
+            eprintln!("Request failed: {}", e);
+        }
+    }
+}
+
+/// Reads a request from the stream and writes the response.
+fn handle(mut stream: TcpStream, scores: &mut Scores, file: &str) -> Result<(), String> {
+    let reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + REQUEST_TIMEOUT,
+    });
+    let (status, body) = match read_request(reader) {
+        Ok(request) => route(&request, scores, file),
+        Err(e) => ("400 Bad Request", error_json(&e)),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the status and body of the response to the request.
+fn route(request: &Request, scores: &mut Scores, file: &str) -> (&'static str, String) {
+    let query = |key: &str| request.query.get(key).cloned().unwrap_or_default();
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/scores") => {
+            let entry = match ScoreEntry::deserialize_json(&request.body) {
+                Ok(entry) => entry,
+                Err(e) => return ("400 Bad Request", error_json(&e.to_string())),
+            };
+            if entry.name.is_empty()
+                || entry.name.chars().count() > MAX_NAME_LEN
+                || !entry.score.is_finite()
+                || entry.score < 0.0
+            {
+                return ("400 Bad Request", error_json("invalid score entry"));
+            }
+
+            let rank = rank(scores, entry.score, &entry.difficulty, &entry.mode);
+            scores.scores.push(entry);
+            if let Err(e) = save(scores, file) {
+                eprintln!("Failed to save {}: {}", file, e);
+            }
+            ("200 OK", RankResponse { rank }.serialize_json())
+        }
+        ("GET", "/scores") => {
+            let count = query("count").parse().unwrap_or(10).min(MAX_COUNT);
+            let (difficulty, mode) = (query("difficulty"), query("mode"));
+
+            let mut top: Vec<ScoreEntry> = scores
+                .scores
+                .iter()
+                .filter(|s| s.difficulty == difficulty && s.mode == mode)
+                .cloned()
+                .collect();
+            top.sort_by(|a, b| b.score.total_cmp(&a.score));
+            top.truncate(count);
+            ("200 OK", Scores { scores: top }.serialize_json())
+        }
+        ("GET", "/rank") => match query("score").parse::<f32>() {
+            Ok(score) => {
+                let rank = rank(scores, score, &query("difficulty"), &query("mode"));
+                ("200 OK", RankResponse { rank }.serialize_json())
+            }
+            Err(_) => ("400 Bad Request", error_json("invalid score")),
+        },
+        _ => ("404 Not Found", error_json("not found")),
+    }
+}
+
+/// Returns the rank the score has among the scores of the same difficulty and mode.
+fn rank(scores: &Scores, score: f32, difficulty: &str, mode: &str) -> usize {
+    1 + scores
+        .scores
+        .iter()
+        .filter(|s| s.difficulty == difficulty && s.mode == mode && s.score > score)
+        .count()
+}
+
+/// Saves the scores to the file, through a temporary file so it's never left half written.
+fn save(scores: &Scores, file: &str) -> std::io::Result<()> {
+    let tmp = format!("{}.tmp", file);
+    fs::write(&tmp, scores.serialize_json())?;
+    fs::rename(tmp, file)
+}
+
+/// Reads from a stream until a deadline, so a slow client can't hold up the server.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"));
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Reads the request line, headers and body of a request.
+fn read_request(mut reader: impl BufRead) -> Result<Request, String> {
+    let mut head_left = MAX_HEAD_LEN;
+    let line = read_head_line(&mut reader, &mut head_left)?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or("missing method")?.to_string();
+    let target = parts.next().ok_or("missing path")?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    // Only the body length is needed from the headers
+    let mut content_length = 0;
+    loop {
+        let header = read_head_line(&mut reader, &mut head_left)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| "invalid content length")?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_LEN {
+        return Err("body too large".to_string());
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (decode(key), decode(value)))
+            .collect(),
+        body: String::from_utf8(body).map_err(|e| e.to_string())?,
+    })
+}
+
+/// Reads a line of the request line and headers, `left` is how many more bytes
+/// they can take up.
+fn read_head_line(reader: &mut impl BufRead, left: &mut usize) -> Result<String, String> {
+    let mut line = String::new();
+    let read = reader
+        .take(*left as u64)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    if read == *left && !line.ends_with('\n') {
+        return Err("headers too large".to_string());
+    }
+    *left -= read;
+    Ok(line)
+}
+
+/// Decodes a percent-encoded query string value.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        decoded.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns the JSON body of an error response.
+fn error_json(message: &str) -> String {
+    ErrorResponse {
+        error: message.to_string(),
+    }
+    .serialize_json()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_request() {
+        let request = "POST /scores?difficulty=Hard&mode=Time%20Attack&name=a+b HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            content-length: 7\r\n\
+            \r\n\
+            {\"a\":1}";
+        let request = read_request(request.as_bytes()).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/scores");
+        assert_eq!(request.query["difficulty"], "Hard");
+        assert_eq!(request.query["mode"], "Time Attack");
+        assert_eq!(request.query["name"], "a b");
+        assert_eq!(request.body, "{\"a\":1}");
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert!(read_request(&b""[..]).is_err());
+        let too_long = format!("Content-Length: {}\r\n\r\n", MAX_BODY_LEN + 1);
+        assert!(read_request(format!("POST /scores HTTP/1.1\r\n{}", too_long).as_bytes()).is_err());
+        let huge_header = format!("X-Pad: {}\r\n\r\n", "a".repeat(MAX_HEAD_LEN));
+        assert!(read_request(format!("GET / HTTP/1.1\r\n{}", huge_header).as_bytes()).is_err());
+        let short_body = "POST /scores HTTP/1.1\r\nContent-Length: 10\r\n\r\n{}";
+        assert!(read_request(short_body.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn decodes_percent_encoding() {
+        assert_eq!(decode("J%C3%BCrgen"), "Jürgen");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,234 @@
+//! Achievements
+//!
+//! Achievements defined in `assets/achievements.ron` and unlocked by observing the
+//! game core events. The unlocked ones are kept with the save data and announced
+//! with a toast.
+
+use super::{game_core::*, stats::Stats};
+use macroquad::file::load_string;
+use nanoserde::DeRon;
+use std::collections::VecDeque;
+
+/// Path of the achievements file, relative to the assets folder.
+const ACHIEVEMENTS_PATH: &str = "achievements.ron";
+/// How long a toast stays on screen, in seconds.
+pub const TOAST_TIME: f32 = 3.0;
+
+/// What has to happen to unlock an achievement.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub enum Condition {
+    /// Kill this many targets in total.
+    Kills(u32),
+    /// Kill this many wrong characters in total.
+    WrongKills(u32),
+    /// Run out of time this many times in total.
+    Timeouts(u32),
+    /// Play this many runs in total.
+    Runs(u32),
+    /// Reach this level in a run.
+    Level(u32),
+    /// Reach this score in a run.
+    Score(f32),
+    /// Kill this many targets in a row without a wrong kill.
+    Streak(u32),
+    /// Kill a target with more than this share (0 to 1) of the level time left.
+    FastKill(f32),
+    /// Kill this many targets in a row without missing a shot.
+    CleanKills(u32),
+}
+
+/// An achievement definition.
+#[derive(Clone, Debug, DeRon)]
+pub struct Achievement {
+    /// Unique id, used to save the unlock state.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+pub struct Achievements {
+    /// The achievement definitions.
+    pub list: Vec<Achievement>,
+    /// Targets killed in a row without missing a shot this run.
+    clean_kills: u32,
+    /// Indices of the unlocked achievements waiting to be shown.
+    toasts: VecDeque<usize>,
+    /// Time left showing the first toast.
+    toast_timer: f32,
+}
+
+impl Achievements {
+    /// Loads the achievement definitions from the assets folder.
+    ///
+    /// Prints the error and goes without achievements if the file is missing or invalid.
+    pub async fn load() -> Achievements {
+        let list = match load_string(ACHIEVEMENTS_PATH).await {
+            Ok(ron) => Vec::<Achievement>::deserialize_ron(&ron).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let list = list.unwrap_or_else(|e| {
+            eprintln!("Invalid {}, no achievements: {}", ACHIEVEMENTS_PATH, e);
+            Vec::new()
+        });
+
+        Achievements {
+            list,
+            clean_kills: 0,
+            toasts: VecDeque::new(),
+            toast_timer: TOAST_TIME,
+        }
+    }
+
+    /// Resets the per run progress.
+    pub fn start_run(&mut self) {
+        self.clean_kills = 0;
+    }
+
+    /// Checks the events of a game core step for newly unlocked achievements,
+    /// adding their ids to `unlocked` and queueing their toasts.
+    ///
+    /// `stats` should already include the events.
+    pub fn observe(
+        &mut self,
+        events: &[CoreEvent],
+        core: &GameCore,
+        stats: &Stats,
+        unlocked: &mut Vec<String>,
+    ) {
+        for (i, event) in events.iter().enumerate() {
+            match event {
+                CoreEvent::Spawned(_) => continue,
+                // A shot always comes right before the kill it made
+                CoreEvent::Shot(..)
+                    if !matches!(events.get(i + 1), Some(CoreEvent::TargetKilled(_))) =>
+                {
+                    self.clean_kills = 0;
+                }
+                CoreEvent::TargetKilled(_) => self.clean_kills += 1,
+                _ => {}
+            }
+
+            for (index, achievement) in self.list.iter().enumerate() {
+                if !unlocked.contains(&achievement.id)
+                    && self.is_met(&achievement.condition, event, core, stats)
+                {
+                    unlocked.push(achievement.id.clone());
+                    self.toasts.push_back(index);
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the condition is met after the event.
+    fn is_met(
+        &self,
+        condition: &Condition,
+        event: &CoreEvent,
+        core: &GameCore,
+        stats: &Stats,
+    ) -> bool {
+        let killed = matches!(event, CoreEvent::TargetKilled(_));
+        match *condition {
+            Condition::Kills(n) => stats.kills >= n,
+            Condition::WrongKills(n) => stats.wrong_kills >= n,
+            Condition::Timeouts(n) => stats.timeouts >= n,
+            Condition::Runs(n) => stats.runs >= n,
+            Condition::Level(n) => core.score[0] >= n as f32,
+            Condition::Score(score) => core.score[1] >= score,
+            Condition::Streak(n) => core.streak >= n,
+            // The time attack clock isn't a level time
+            Condition::FastKill(share) => {
+                killed
+                    && core.mode != GameMode::TimeAttack
+                    && core.level.timer / core.level.time_limit > share
+            }
+            Condition::CleanKills(n) => self.clean_kills >= n,
+        }
+    }
+
+    /// Advances the toast timer by `dt` seconds and returns the achievement to show, if any.
+    pub fn update_toast(&mut self, dt: f32) -> Option<(&Achievement, f32)> {
+        let index = *self.toasts.front()?;
+        self.toast_timer -= dt;
+        if self.toast_timer <= 0.0 {
+            self.toasts.pop_front();
+            self.toast_timer = TOAST_TIME;
+            return None;
+        }
+        Some((&self.list[index], self.toast_timer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_core::tests::{indices, shoot, spawn_crowd, start};
+
+    fn achievements(conditions: &[Condition]) -> Achievements {
+        let list = (conditions.iter().enumerate())
+            .map(|(i, condition)| Achievement {
+                id: i.to_string(),
+                name: String::new(),
+                description: String::new(),
+                condition: condition.clone(),
+            })
+            .collect();
+        Achievements {
+            list,
+            clean_kills: 0,
+            toasts: VecDeque::new(),
+            toast_timer: TOAST_TIME,
+        }
+    }
+
+    #[test]
+    fn the_shipped_achievements_parse() {
+        let ron = std::fs::read_to_string(format!("assets/{}", ACHIEVEMENTS_PATH)).unwrap();
+        let list = Vec::<Achievement>::deserialize_ron(&ron).unwrap();
+        assert!(!list.is_empty());
+        for (i, achievement) in list.iter().enumerate() {
+            assert!(list[..i].iter().all(|a| a.id != achievement.id));
+        }
+    }
+
+    #[test]
+    fn unlocks_from_the_core_events() {
+        let mut achievements = achievements(&[
+            Condition::Kills(1),
+            Condition::Level(2),
+            Condition::Streak(2),
+            Condition::CleanKills(2),
+            Condition::WrongKills(1),
+        ]);
+        let (mut stats, mut unlocked) = (Stats::default(), Vec::new());
+        let mut core = start(3, GameMode::Classic);
+        let mut observe = |events: Vec<CoreEvent>, core: &GameCore, stats: &mut Stats| {
+            // Kills and wrong kills are counted by the stats, see `Stats::record`
+            for event in &events {
+                match event {
+                    CoreEvent::TargetKilled(_) => stats.kills += 1,
+                    CoreEvent::WrongKill(_) => stats.wrong_kills += 1,
+                    _ => {}
+                }
+            }
+            achievements.observe(&events, core, stats, &mut unlocked);
+            unlocked.clone()
+        };
+
+        spawn_crowd(&mut core);
+        let target = indices(&core, true)[0];
+        let events = shoot(&mut core, target);
+        assert_eq!(observe(events, &core, &mut stats), ["0"]);
+
+        // A missed shot breaks the clean kills, not the streak
+        core.set_level();
+        spawn_crowd(&mut core);
+        let events = core.step(0.0, &[InputEvent::Shoot(-100.0, -100.0)]);
+        observe(events, &core, &mut stats);
+        let target = indices(&core, true)[0];
+        let events = shoot(&mut core, target);
+        assert_eq!(observe(events, &core, &mut stats), ["0", "1", "2"]);
+        assert_eq!(achievements.update_toast(0.0).unwrap().0.id, "0");
+    }
+}
@@ -1,24 +1,62 @@
+//! Asset bundle
+//!
+//! Game assets, loaded from the files listed in `assets/manifest.ron` and looked up
+//! by id. The manifest also lists the trait categories and their variants.
+
+use super::palette::Palette;
+use super::skins::{load_skin_packs, Skin, DEFAULT_SKIN};
+use super::traits::{self, TraitCategory, TraitRegistry};
 use super::utils::colors::*;
 use super::utils::text::*;
 use macroquad::audio::*;
+use macroquad::file::{load_file, load_string};
+use macroquad::miniquad;
 use macroquad::prelude::*;
+use nanoserde::DeRon;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
 
-pub const ARMS_COUNT: usize = 9;
-pub const BODY_COUNT: usize = 10;
-pub const FACE_COUNT: usize = 13;
-pub const HAT_COUNT: usize = 10;
-pub const LEGS_COUNT: usize = 5;
-pub const CHAR_PARTS_COUNT: usize = 5;
+/// Path of the asset manifest, relative to the assets folder.
+const MANIFEST_PATH: &str = "manifest.ron";
+/// The ids the game looks assets up by, and their kind.
+const ASSET_IDS: [(&str, AssetKind); 17] = [
+    ("ground", AssetKind::Texture),
+    ("crosshair", AssetKind::Texture),
+    ("logo", AssetKind::Texture),
+    ("frame", AssetKind::Texture),
+    ("frame_long", AssetKind::Texture),
+    ("bar", AssetKind::Texture),
+    ("bar_bg", AssetKind::Texture),
+    ("skull", AssetKind::Texture),
+    ("blood", AssetKind::Texture),
+    ("empty", AssetKind::Texture),
+    ("bg_music", AssetKind::Sound),
+    ("spawn", AssetKind::Sound),
+    ("shoot", AssetKind::Sound),
+    ("game_over", AssetKind::Sound),
+    ("menu_in", AssetKind::Sound),
+    ("menu_out", AssetKind::Sound),
+    ("pause", AssetKind::Sound),
+];
 
 /// Game assets.
 pub struct AssetBundle {
     pub ground: Texture2D,
     pub crosshair: Texture2D,
-    pub char_arms: [Texture2D; ARMS_COUNT],
-    pub char_body: [Texture2D; BODY_COUNT],
-    pub char_face: [Texture2D; FACE_COUNT],
-    pub char_hat: [Texture2D; HAT_COUNT],
-    pub char_legs: [Texture2D; LEGS_COUNT],
+    /// The trait categories characters are made of.
+    pub traits: TraitRegistry,
+    /// The character part sets, the default one first. Each one is packed into one
+    /// texture, so the crowd can be drawn in a few draw calls.
+    pub skins: Vec<Skin>,
+    /// Index of the skin the characters are drawn with.
+    skin: Cell<usize>,
+    /// The palette the characters and the interface are drawn with.
+    palette: Cell<Palette>,
+    /// Whether the character tints are drawn with pattern markers.
+    markers: Cell<bool>,
+    /// Whether the hints describe the target in words instead of pictures.
+    word_hints: Cell<bool>,
     pub logo: Texture2D,
     pub frame: Texture2D,
     pub frame_long: Texture2D,
@@ -36,59 +74,195 @@ pub struct AssetBundle {
     pub pause_sound: Sound,
 }
 
-enum Asset {
-    Texture(Texture2D),
-    Sound(Sound),
+/// The kind of file an asset is loaded from.
+#[derive(Clone, Copy, Debug, PartialEq, DeRon)]
+pub enum AssetKind {
+    Texture,
+    Sound,
 }
 
-impl AssetBundle {
-    /// Loads all game assets.
-    pub async fn load() -> Option<AssetBundle> {
-        let font = load_ttf_font("04B03.TTF").await.unwrap();
-        let mut asset_paths = vec![
-            "images/ground.png".to_string(),
-            "images/crosshair.png".to_string(),
-            "images/logo.png".to_string(),
-            "images/frame.png".to_string(),
-            "images/frame-long.png".to_string(),
-            "images/bar.png".to_string(),
-            "images/bar-bg.png".to_string(),
-            "images/skull.png".to_string(),
-            "images/blood.png".to_string(),
-            "images/empty.png".to_string(),
-            "audio/puzzle_pieces.wav".to_string(),
-            "audio/spawn.wav".to_string(),
-            "audio/shoot.wav".to_string(),
-            "audio/evil_laugh.wav".to_string(),
-            "audio/menu_in.wav".to_string(),
-            "audio/menu_out.wav".to_string(),
-            "audio/pause.wav".to_string(),
-        ];
-        for i in 0..ARMS_COUNT {
-            asset_paths.push(format!("images/character/arms-{}.png", i));
-        }
-        for i in 0..BODY_COUNT {
-            asset_paths.push(format!("images/character/body-{}.png", i));
+/// Texture filter mode of an asset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, DeRon)]
+pub enum Filter {
+    /// Keeps the pixel art sharp.
+    #[default]
+    Nearest,
+    Linear,
+}
+
+/// An asset listed in the manifest.
+#[derive(Clone, Debug, DeRon)]
+pub struct AssetEntry {
+    /// Unique id the asset is looked up by.
+    pub id: String,
+    /// Path of the file, relative to the assets folder.
+    pub path: String,
+    pub kind: AssetKind,
+    /// Ignored for sounds.
+    #[nserde(default)]
+    pub filter: Filter,
+    /// Whether a placeholder can stand in for the asset if it's missing.
+    #[nserde(default)]
+    pub optional: bool,
+}
+
+/// Variants of a trait category listed in a skin pack.
+#[derive(Clone, Debug, DeRon)]
+pub struct PartEntry {
+    /// Name of the trait category.
+    pub name: String,
+    /// Paths of the variants' textures, relative to the skin pack's folder. The default
+    /// skin's are the manifest's `TraitCategory::variants`, relative to the assets folder.
+    pub variants: Vec<String>,
+    /// The variants' names in the word hints, numbered if there are none.
+    #[nserde(default)]
+    pub names: Vec<String>,
+}
+
+/// The asset manifest, `assets/manifest.ron`.
+#[derive(Clone, Debug, DeRon)]
+pub struct Manifest {
+    /// Path of the font, relative to the assets folder.
+    pub font: String,
+    pub assets: Vec<AssetEntry>,
+    /// The trait categories, in trait order.
+    pub traits: Vec<TraitCategory>,
+}
+
+impl Manifest {
+    /// Parses and validates a manifest.
+    pub fn from_ron(ron: &str) -> Result<Manifest, String> {
+        let manifest = Manifest::deserialize_ron(ron).map_err(|e| e.to_string())?;
+
+        let mut ids: Vec<&str> = manifest.assets.iter().map(|a| a.id.as_str()).collect();
+        ids.sort_unstable();
+        if let Some(pair) = ids.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(format!("asset id \"{}\" is listed more than once", pair[0]));
         }
-        for i in 0..FACE_COUNT {
-            asset_paths.push(format!("images/character/face-{}.png", i));
+        for (id, kind) in ASSET_IDS {
+            if !manifest.assets.iter().any(|a| a.id == id && a.kind == kind) {
+                return Err(format!("no {:?} asset with id \"{}\"", kind, id));
+            }
         }
-        for i in 0..HAT_COUNT {
-            asset_paths.push(format!("images/character/hat-{}.png", i));
+
+        traits::validate(&manifest.traits)?;
+
+        Ok(manifest)
+    }
+
+    /// Returns everything to load: the assets, then the trait variants with ids like `arms-0`.
+    fn jobs(&self) -> Vec<LoadJob> {
+        let mut jobs: Vec<LoadJob> = self
+            .assets
+            .iter()
+            .map(|entry| LoadJob {
+                id: entry.id.clone(),
+                path: entry.path.clone(),
+                load: match entry.kind {
+                    AssetKind::Texture => Load::Texture(entry.filter),
+                    AssetKind::Sound => Load::Sound,
+                },
+                optional: entry.optional,
+            })
+            .collect();
+        for category in &self.traits {
+            for (i, path) in category.variants.iter().enumerate() {
+                jobs.push(LoadJob {
+                    id: part_id(&category.name, i),
+                    path: path.clone(),
+                    load: Load::Image,
+                    optional: false,
+                });
+            }
         }
-        for i in 0..LEGS_COUNT {
-            asset_paths.push(format!("images/character/legs-{}.png", i));
+        jobs
+    }
+}
+
+/// A file to load.
+struct LoadJob {
+    id: String,
+    path: String,
+    load: Load,
+    optional: bool,
+}
+
+/// What to load a file as.
+#[derive(Clone, Copy)]
+enum Load {
+    Texture(Filter),
+    Sound,
+    /// An image kept in memory, to be packed into the character atlas.
+    Image,
+}
+
+/// A file that couldn't be loaded.
+#[derive(Clone, Debug)]
+pub struct AssetError {
+    /// Path of the file, relative to the assets folder.
+    pub path: String,
+    /// Why it couldn't be loaded.
+    pub cause: String,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.cause)
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+impl AssetError {
+    pub fn new(path: &str, cause: impl ToString) -> AssetError {
+        AssetError {
+            path: path.to_string(),
+            cause: cause.to_string(),
         }
+    }
+}
+
+enum Asset {
+    Texture(Texture2D),
+    Sound(Sound),
+    Image(Image),
+}
+
+impl AssetBundle {
+    /// Loads all the game assets listed in the manifest.
+    ///
+    /// Missing optional assets are replaced with placeholders and skin packs that can't
+    /// be loaded are skipped, these are returned with the bundle as warnings. Returns
+    /// every error if the manifest or any other asset can't be loaded.
+    pub async fn load() -> Result<(AssetBundle, Vec<AssetError>), Vec<AssetError>> {
+        let manifest = match load_string(MANIFEST_PATH).await {
+            Ok(ron) => Manifest::from_ron(&ron),
+            Err(e) => Err(e.to_string()),
+        };
+        let manifest = manifest.map_err(|e| vec![AssetError::new(MANIFEST_PATH, e)])?;
+        let mut warnings = Vec::new();
+        let mut errors = Vec::new();
+
+        // The default font is good enough to play with
+        let font = load_ttf_font(&manifest.font).await.unwrap_or_else(|e| {
+            let cause = format!("{}, using the default font", e);
+            warnings.push(AssetError::new(&manifest.font, cause));
+            Font::default()
+        });
+        let jobs = manifest.jobs();
 
         // Load all assets in parallel using coroutines.
         // - Thanks to Osennyaya#4019 for code example.
-        let mut assets_vec: Vec<Asset> = Vec::with_capacity(asset_paths.len());
-        let coroutine_vec: Vec<_> = asset_paths
+        let mut loaded: HashMap<String, Asset> = HashMap::with_capacity(jobs.len());
+        let coroutine_vec: Vec<_> = jobs
             .into_iter()
-            .map(|p| coroutines::start_coroutine(load_asset(p)))
+            .map(|job| {
+                let load = load_asset(job.path.clone(), job.load);
+                (job, coroutines::start_coroutine(load))
+            })
             .collect();
 
-        for c in coroutine_vec {
+        for (job, c) in coroutine_vec {
             while !c.is_done() {
                 clear_background(BG_PURPLE);
                 draw_text_centered(
@@ -101,96 +275,466 @@ impl AssetBundle {
                 );
                 next_frame().await;
             }
-            if let Some(asset) = c.retrieve() {
-                assets_vec.push(asset);
+            match c.retrieve() {
+                Some(Ok(asset)) => {
+                    loaded.insert(job.id, asset);
+                }
+                Some(Err(error)) if job.optional => match placeholder(job.load).await {
+                    Ok(asset) => {
+                        loaded.insert(job.id, asset);
+                        let cause = format!("{}, using a placeholder", error.cause);
+                        warnings.push(AssetError::new(&error.path, cause));
+                    }
+                    Err(placeholder_error) => errors.extend([error, placeholder_error]),
+                },
+                Some(Err(error)) => errors.push(error),
+                None => errors.push(AssetError::new(&job.path, "loading was interrupted")),
             }
         }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let char_images: Vec<Vec<Image>> = manifest
+            .traits
+            .iter()
+            .map(|category| {
+                (0..category.variants.len())
+                    .map(|i| take_image(&mut loaded, &part_id(&category.name, i)))
+                    .collect()
+            })
+            .collect();
+        let traits = TraitRegistry::new(manifest.traits).unwrap(); // Validated with the manifest
+        let char_names: Vec<Vec<String>> = (traits.categories.iter().zip(&char_images))
+            .map(|(category, images)| {
+                (0..images.len())
+                    .map(|i| category.variant_name(i))
+                    .collect()
+            })
+            .collect();
+        let skin = Skin::pack(
+            DEFAULT_SKIN,
+            MANIFEST_PATH,
+            &char_images,
+            char_names.clone(),
+        );
+        let mut skins = vec![skin.map_err(|e| vec![e])?];
+        let (packs, pack_errors) = load_skin_packs(&char_images, &char_names, &traits);
+        skins.extend(packs);
+        warnings.extend(pack_errors);
 
-        assets_vec.reverse();
-        let mut assets = AssetBundle {
-            ground: pop_texture(&mut assets_vec)?,
-            crosshair: pop_texture(&mut assets_vec)?,
-            logo: pop_texture(&mut assets_vec)?,
-            frame: pop_texture(&mut assets_vec)?,
-            frame_long: pop_texture(&mut assets_vec)?,
-            bar: [pop_texture(&mut assets_vec)?, pop_texture(&mut assets_vec)?],
-            skull: pop_texture(&mut assets_vec)?,
-            blood: pop_texture(&mut assets_vec)?,
-            empty: pop_texture(&mut assets_vec)?,
-            bg_music: pop_sound(&mut assets_vec)?,
-            spawn_sound: pop_sound(&mut assets_vec)?,
-            shoot_sound: pop_sound(&mut assets_vec)?,
-            game_over_sound: pop_sound(&mut assets_vec)?,
-            menu_in_sound: pop_sound(&mut assets_vec)?,
-            menu_out_sound: pop_sound(&mut assets_vec)?,
-            pause_sound: pop_sound(&mut assets_vec)?,
+        let assets = AssetBundle {
+            ground: take_texture(&mut loaded, "ground"),
+            crosshair: take_texture(&mut loaded, "crosshair"),
+            traits,
+            skins,
+            skin: Cell::new(0),
+            palette: Cell::new(Palette::Default),
+            markers: Cell::new(false),
+            word_hints: Cell::new(false),
+            logo: take_texture(&mut loaded, "logo"),
+            frame: take_texture(&mut loaded, "frame"),
+            frame_long: take_texture(&mut loaded, "frame_long"),
             font,
-            char_arms: [Texture2D::empty(); ARMS_COUNT],
-            char_body: [Texture2D::empty(); BODY_COUNT],
-            char_face: [Texture2D::empty(); FACE_COUNT],
-            char_hat: [Texture2D::empty(); HAT_COUNT],
-            char_legs: [Texture2D::empty(); LEGS_COUNT],
+            bar: [
+                take_texture(&mut loaded, "bar"),
+                take_texture(&mut loaded, "bar_bg"),
+            ],
+            skull: take_texture(&mut loaded, "skull"),
+            blood: take_texture(&mut loaded, "blood"),
+            empty: take_texture(&mut loaded, "empty"),
+            bg_music: take_sound(&mut loaded, "bg_music"),
+            spawn_sound: take_sound(&mut loaded, "spawn"),
+            shoot_sound: take_sound(&mut loaded, "shoot"),
+            game_over_sound: take_sound(&mut loaded, "game_over"),
+            menu_in_sound: take_sound(&mut loaded, "menu_in"),
+            menu_out_sound: take_sound(&mut loaded, "menu_out"),
+            pause_sound: take_sound(&mut loaded, "pause"),
         };
+        Ok((assets, warnings))
+    }
+
+    /// Draws the given variant of a trait category, stretched to `size`.
+    pub fn draw_char_part(
+        &self,
+        part: usize,
+        variant: usize,
+        x: f32,
+        y: f32,
+        size: Vec2,
+        color: Color,
+    ) {
+        let skin = self.skin();
+        draw_texture_ex(
+            skin.atlas,
+            x,
+            y,
+            color,
+            DrawTextureParams {
+                dest_size: Some(size),
+                source: Some(skin.rects[part][variant]),
+                ..Default::default()
+            },
+        );
+    }
 
-        // Set textures filters to nearest for better pixel art rendering.
-        assets.ground.set_filter(FilterMode::Nearest);
-        assets.crosshair.set_filter(FilterMode::Nearest);
-        assets.logo.set_filter(FilterMode::Nearest);
-        assets.frame.set_filter(FilterMode::Nearest);
-        assets.frame_long.set_filter(FilterMode::Nearest);
-        assets.bar[0].set_filter(FilterMode::Nearest);
-        assets.bar[1].set_filter(FilterMode::Nearest);
-        assets.skull.set_filter(FilterMode::Nearest);
-        assets.blood.set_filter(FilterMode::Nearest);
-        assets.empty.set_filter(FilterMode::Nearest);
-
-        for i in 0..ARMS_COUNT {
-            assets.char_arms[i] = pop_texture(&mut assets_vec)?;
-            assets.char_arms[i].set_filter(FilterMode::Nearest);
+    /// Returns the number of variants of each trait category in the current skin.
+    /// Skins don't change the colors.
+    pub fn part_counts(&self) -> Vec<usize> {
+        let rects = &self.skin().rects;
+        (self.traits.categories.iter().zip(rects))
+            .map(|(category, rects)| match category.is_color() {
+                true => category.colors.len(),
+                false => rects.len(),
+            })
+            .collect()
+    }
+
+    /// Returns the name of the given variant of a trait category in the word hints,
+    /// from the current skin, or from the palette for a color.
+    pub fn variant_name(&self, part: usize, variant: usize) -> String {
+        let category = self.traits.get(part);
+        if category.is_color() {
+            let name = category.variant_name(variant);
+            return match self.palette().color_name(&name) {
+                Some(name) => name.to_string(),
+                None => name,
+            };
         }
-        for i in 0..BODY_COUNT {
-            assets.char_body[i] = pop_texture(&mut assets_vec)?;
-            assets.char_body[i].set_filter(FilterMode::Nearest);
+        self.skin().names[part][variant].clone()
+    }
+
+    /// Returns the skin the characters are drawn with.
+    pub fn skin(&self) -> &Skin {
+        &self.skins[self.skin.get()]
+    }
+
+    /// Returns the index of the skin the characters are drawn with.
+    pub fn skin_index(&self) -> usize {
+        self.skin.get()
+    }
+
+    /// Returns the palette the characters and the interface are drawn with.
+    pub fn palette(&self) -> Palette {
+        self.palette.get()
+    }
+
+    /// Draws the characters and the interface with the given palette.
+    pub fn set_palette(&self, palette: Palette) {
+        self.palette.set(palette);
+    }
+
+    /// Returns whether the character tints are drawn with pattern markers.
+    pub fn markers(&self) -> bool {
+        self.markers.get()
+    }
+
+    /// Turns the pattern markers of the character tints on or off.
+    pub fn set_markers(&self, markers: bool) {
+        self.markers.set(markers);
+    }
+
+    /// Returns whether the hints describe the target in words instead of pictures.
+    pub fn word_hints(&self) -> bool {
+        self.word_hints.get()
+    }
+
+    /// Switches the hints between words and pictures.
+    pub fn set_word_hints(&self, word_hints: bool) {
+        self.word_hints.set(word_hints);
+    }
+
+    /// Draws the characters with the skin with the given name.
+    ///
+    /// Returns `false` and keeps the current skin if there's no such skin.
+    pub fn set_skin(&self, name: &str) -> bool {
+        match self.skins.iter().position(|skin| skin.name == name) {
+            Some(index) => {
+                self.skin.set(index);
+                true
+            }
+            None => false,
         }
-        for i in 0..FACE_COUNT {
-            assets.char_face[i] = pop_texture(&mut assets_vec)?;
-            assets.char_face[i].set_filter(FilterMode::Nearest);
+    }
+}
+
+/// Shows the assets that couldn't be loaded until Escape is pressed.
+pub async fn show_asset_errors(errors: &[AssetError]) {
+    const FONT_SIZE: f32 = 24.0;
+    while !is_key_pressed(KeyCode::Escape) {
+        clear_background(BG_PURPLE);
+        let x = 40.0;
+        let mut y = 60.0;
+        draw_text(
+            "The game assets couldn't be loaded",
+            x,
+            y,
+            40.0,
+            COLOR_YELLOW,
+        );
+        y += 50.0;
+
+        let max_lines = ((screen_height() - y - 60.0) / FONT_SIZE).max(1.0) as usize;
+        for (i, error) in errors.iter().enumerate() {
+            if i + 1 == max_lines && errors.len() > max_lines {
+                let more = format!("...and {} more", errors.len() - i);
+                draw_text(&more, x, y, FONT_SIZE, WHITE);
+                break;
+            }
+            draw_text(&error.to_string(), x, y, FONT_SIZE, WHITE);
+            y += FONT_SIZE;
         }
-        for i in 0..HAT_COUNT {
-            assets.char_hat[i] = pop_texture(&mut assets_vec)?;
-            assets.char_hat[i].set_filter(FilterMode::Nearest);
+
+        draw_text(
+            "Check the assets folder and restart the game. Press Escape to quit.",
+            x,
+            screen_height() - 40.0,
+            FONT_SIZE,
+            WHITE,
+        );
+        next_frame().await;
+    }
+}
+
+/// Returns the id of a trait variant, like `arms-0`.
+fn part_id(part: &str, variant: usize) -> String {
+    format!("{}-{}", part.to_lowercase(), variant)
+}
+
+/// Takes the texture with the given id out of the loaded assets.
+fn take_texture(assets: &mut HashMap<String, Asset>, id: &str) -> Texture2D {
+    // The ids and kinds are checked when the manifest is loaded
+    match assets.remove(id) {
+        Some(Asset::Texture(t)) => t,
+        _ => unreachable!("texture \"{}\" wasn't loaded", id),
+    }
+}
+
+/// Takes the image with the given id out of the loaded assets.
+fn take_image(assets: &mut HashMap<String, Asset>, id: &str) -> Image {
+    match assets.remove(id) {
+        Some(Asset::Image(image)) => image,
+        _ => unreachable!("image \"{}\" wasn't loaded", id),
+    }
+}
+
+/// Takes the sound with the given id out of the loaded assets.
+fn take_sound(assets: &mut HashMap<String, Asset>, id: &str) -> Sound {
+    match assets.remove(id) {
+        Some(Asset::Sound(s)) => s,
+        _ => unreachable!("sound \"{}\" wasn't loaded", id),
+    }
+}
+
+/// Load an asset from the given path.
+async fn load_asset(path: String, load: Load) -> Result<Asset, AssetError> {
+    let bytes = load_file(&path).await.map_err(|e| {
+        let cause = match e.kind {
+            miniquad::fs::Error::IOError(e) => e.to_string(),
+            kind => format!("{:?}", kind),
+        };
+        AssetError::new(&path, cause)
+    })?;
+    let error = |cause: String| AssetError::new(&path, cause);
+
+    match load {
+        Load::Texture(filter) => {
+            let texture = Texture2D::from_image(&decode_image(&bytes).map_err(error)?);
+            texture.set_filter(filter_mode(filter));
+            Ok(Asset::Texture(texture))
         }
-        for i in 0..LEGS_COUNT {
-            assets.char_legs[i] = pop_texture(&mut assets_vec)?;
-            assets.char_legs[i].set_filter(FilterMode::Nearest);
+        Load::Sound => {
+            #[cfg(not(target_arch = "wasm32"))]
+            check_sound(&bytes).map_err(error)?;
+            load_sound_from_bytes(&bytes)
+                .await
+                .map(Asset::Sound)
+                .map_err(|e| error(e.to_string()))
         }
+        Load::Image => Ok(Asset::Image(decode_image(&bytes).map_err(error)?)),
+    }
+}
+
+/// Decodes a PNG or TGA image, unlike `Image::from_file_with_format` which panics on
+/// anything it can't decode.
+pub fn decode_image(bytes: &[u8]) -> Result<Image, String> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(format!("image is too large ({}x{})", width, height));
+    }
+    Ok(Image {
+        width: width as u16,
+        height: height as u16,
+        bytes: image.into_raw(),
+    })
+}
+
+/// Checks that a sound can be decoded, quad-snd panics on sounds it can't.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_sound(bytes: &[u8]) -> Result<(), String> {
+    let mut reader = audrey::Reader::new(std::io::Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let description = reader.description();
+    let channels = description.channel_count();
+    if channels != 1 && channels != 2 {
+        return Err(format!("unsupported channel count {}", channels));
+    }
+    if description.sample_rate() == 0 {
+        return Err("invalid sample rate 0".to_string());
+    }
+    let mut samples = 0;
+    for sample in reader.samples::<f32>() {
+        sample.map_err(|e| e.to_string())?;
+        samples += 1;
+    }
+    if samples % channels != 0 {
+        return Err("truncated sound".to_string());
+    }
+    Ok(())
+}
 
-        Some(assets)
+/// Returns the macroquad filter mode of a manifest filter mode.
+fn filter_mode(filter: Filter) -> FilterMode {
+    match filter {
+        Filter::Nearest => FilterMode::Nearest,
+        Filter::Linear => FilterMode::Linear,
     }
 }
 
-/// Pop a texture from the assets vector.
-fn pop_texture(assets: &mut Vec<Asset>) -> Option<Texture2D> {
-    match assets.pop().unwrap() {
-        Asset::Texture(t) => Some(t),
-        _ => None,
+/// Returns a placeholder for a missing asset: a magenta checkerboard or a short silence.
+async fn placeholder(load: Load) -> Result<Asset, AssetError> {
+    match load {
+        Load::Texture(filter) => {
+            let texture = Texture2D::from_image(&placeholder_image());
+            texture.set_filter(filter_mode(filter));
+            Ok(Asset::Texture(texture))
+        }
+        Load::Sound => load_sound_from_bytes(&silent_wav())
+            .await
+            .map(Asset::Sound)
+            .map_err(|e| AssetError::new("(silent placeholder)", e)),
+        Load::Image => Ok(Asset::Image(placeholder_image())),
     }
 }
 
-/// Pop a sound from the assets vector.
-fn pop_sound(assets: &mut Vec<Asset>) -> Option<Sound> {
-    match assets.pop().unwrap() {
-        Asset::Sound(s) => Some(s),
-        _ => None,
+/// Returns a magenta and black checkerboard.
+fn placeholder_image() -> Image {
+    const SIZE: u16 = 8;
+    let mut image = Image::gen_image_color(SIZE, SIZE, BLACK);
+    for y in 0..SIZE as u32 {
+        for x in 0..SIZE as u32 {
+            if (x / 2 + y / 2) % 2 == 0 {
+                image.set_pixel(x, y, MAGENTA);
+            }
+        }
     }
+    image
 }
 
-/// Load asset from the given path.
-async fn load_asset(path: String) -> Asset {
-    let ext = path.split('.').last().unwrap();
-    match ext {
-        "png" => Asset::Texture(load_texture(path.as_str()).await.unwrap()),
-        "wav" => Asset::Sound(load_sound(path.as_str()).await.unwrap()),
-        _ => panic!("Unknown file extension"),
+/// Packs the images of each trait category into an atlas, returning it with where
+/// each image ended up.
+///
+/// The images are laid out on a grid of cells as big as the biggest image, with a
+/// transparent pixel between cells so they don't bleed into each other. Fails, with
+/// the error reported against `path`, if the grid is too big for an image.
+pub fn pack_atlas(
+    path: &str,
+    images: &[Vec<Image>],
+) -> Result<(Image, Vec<Vec<Rect>>), AssetError> {
+    let all = || images.iter().flatten();
+    let cell_width = all().map(|i| i.width).max().unwrap_or(1) as u32 + 1;
+    let cell_height = all().map(|i| i.height).max().unwrap_or(1) as u32 + 1;
+    let columns = (all().count() as f32).sqrt().ceil().max(1.0) as u32;
+    let rows = (all().count() as u32).div_ceil(columns).max(1);
+
+    let (width, height) = (columns * cell_width, rows * cell_height);
+    let too_big = |_| {
+        let cause = format!(
+            "the character parts need a {}x{} atlas, more than the {} pixels a side can fit",
+            width,
+            height,
+            u16::MAX
+        );
+        AssetError::new(path, cause)
+    };
+    let mut atlas = Image::gen_image_color(
+        u16::try_from(width).map_err(too_big)?,
+        u16::try_from(height).map_err(too_big)?,
+        BLANK,
+    );
+    let atlas_width = atlas.width as usize;
+    let pixels = atlas.get_image_data_mut();
+    let mut rects = vec![Vec::new(); images.len()];
+    for (i, (image, part)) in images
+        .iter()
+        .enumerate()
+        .flat_map(|(part, variants)| variants.iter().map(move |image| (image, part)))
+        .enumerate()
+    {
+        let (x, y) = (
+            i as u32 % columns * cell_width,
+            i as u32 / columns * cell_height,
+        );
+        for (dy, row) in image
+            .get_image_data()
+            .chunks(image.width as usize)
+            .enumerate()
+        {
+            let start = (y as usize + dy) * atlas_width + x as usize;
+            pixels[start..start + row.len()].copy_from_slice(row);
+        }
+        let (width, height) = (image.width as f32, image.height as f32);
+        rects[part].push(Rect::new(x as f32, y as f32, width, height));
+    }
+
+    Ok((atlas, rects))
+}
+
+/// Returns a WAV file of a tenth of a second of silence, 16 bit mono at 44.1 kHz.
+fn silent_wav() -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 44100;
+    let data_len = SAMPLE_RATE / 10 * 2;
+
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend(b"RIFF");
+    wav.extend((36 + data_len).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes()); // Format chunk size
+    wav.extend(1u16.to_le_bytes()); // PCM
+    wav.extend(1u16.to_le_bytes()); // Channels
+    wav.extend(SAMPLE_RATE.to_le_bytes());
+    wav.extend((SAMPLE_RATE * 2).to_le_bytes()); // Bytes per second
+    wav.extend(2u16.to_le_bytes()); // Bytes per frame
+    wav.extend(16u16.to_le_bytes()); // Bits per sample
+    wav.extend(b"data");
+    wav.extend(data_len.to_le_bytes());
+    wav.resize(44 + data_len as usize, 0);
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_the_parts_on_a_grid() {
+        let image = |width, height| Image::gen_image_color(width, height, WHITE);
+        let images = vec![vec![image(4, 2), image(3, 3)], vec![image(2, 2)]];
+        let (atlas, rects) = pack_atlas("test", &images).unwrap();
+        assert_eq!((atlas.width, atlas.height), (10, 8));
+        assert_eq!(
+            rects[0],
+            [Rect::new(0.0, 0.0, 4.0, 2.0), Rect::new(5.0, 0.0, 3.0, 3.0)]
+        );
+        assert_eq!(rects[1], [Rect::new(0.0, 4.0, 2.0, 2.0)]);
+    }
+
+    #[test]
+    fn an_atlas_too_big_for_an_image_fails() {
+        let wide = Image::gen_image_color(40000, 1, WHITE);
+        let error = pack_atlas("test", &[vec![wide.clone(), wide]]).unwrap_err();
+        assert_eq!(error.path, "test");
     }
 }
@@ -0,0 +1,465 @@
+//! Game core
+//!
+//! Headless game simulation. Owns the level, score, spawn and timer logic and is
+//! advanced with `step`, without drawing anything or reading input directly.
+
+use super::*;
+use nanoserde::{DeJson, SerJson};
+
+/// Player input fed into the game core.
+#[derive(Clone, Copy, Debug, PartialEq, SerJson, DeJson)]
+pub enum InputEvent {
+    /// Shoot at the given position (in render target coordinates).
+    Shoot(f32, f32),
+    /// Move the keyboard selection to the nearest character in the given direction.
+    Select(Direction),
+    /// Shoot the selected character.
+    ShootSelected,
+}
+
+/// Direction to move the keyboard selection in.
+#[derive(Clone, Copy, Debug, PartialEq, SerJson, DeJson)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Game mode, the rules of a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, SerJson, DeJson)]
+pub enum GameMode {
+    /// Find the targets before the level timer runs out. One mistake ends the run.
+    #[default]
+    Classic,
+    /// No timer and killing the wrong character doesn't end the run.
+    Practice,
+    /// One clock for the whole run. Kills add time and wrong kills take time away.
+    TimeAttack,
+    /// Classic rules, but every contract has several targets.
+    Contracts,
+}
+
+impl GameMode {
+    /// All the game modes, in the order they are shown on the menu.
+    pub const ALL: [GameMode; 4] = [
+        GameMode::Classic,
+        GameMode::Practice,
+        GameMode::TimeAttack,
+        GameMode::Contracts,
+    ];
+
+    /// Returns the mode's display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Practice => "Practice",
+            GameMode::TimeAttack => "Time Attack",
+            GameMode::Contracts => "Contracts",
+        }
+    }
+
+    /// Returns the next mode in the menu order, wrapping around.
+    pub fn step(self, step: isize) -> GameMode {
+        let len = GameMode::ALL.len() as isize;
+        let i = GameMode::ALL.iter().position(|m| *m == self).unwrap() as isize;
+        GameMode::ALL[(i + step).rem_euclid(len) as usize]
+    }
+
+    /// Returns `true` if runs in this mode can set highscores.
+    pub fn keeps_score(self) -> bool {
+        self != GameMode::Practice
+    }
+}
+
+/// Events produced by the game core while stepping.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoreEvent {
+    /// The character at the given crowd index spawned.
+    Spawned(usize),
+    /// A shot was fired at the given position.
+    Shot(f32, f32),
+    /// A target was killed, the given seconds after the level's timer started or after
+    /// the previous target kill.
+    TargetKilled(f32),
+    /// The last target was killed, clearing the level. Comes right after its
+    /// `TargetKilled`.
+    LevelCleared,
+    /// The non-target character at the given crowd index was killed.
+    /// Ends the run in classic mode, takes time away in time attack.
+    WrongKill(usize),
+    /// The level timer ran out.
+    TimeUp,
+}
+
+pub struct GameCore {
+    /// The level struct.
+    pub level: Level,
+    /// The score, [level number, total score]
+    pub score: [f32; 2],
+    /// The game over flag.
+    pub game_over: bool,
+    /// The seed the current run was started with.
+    pub seed: u64,
+    /// Gameplay tuning values.
+    pub tuning: Rc<Tuning>,
+    /// The difficulty of the run.
+    pub difficulty: Difficulty,
+    /// The game mode of the run.
+    pub mode: GameMode,
+    /// Time spent with the timer running this run, in seconds.
+    pub elapsed: f32,
+    /// Time spent with the timer running this level, in seconds.
+    pub level_elapsed: f32,
+    /// `level_elapsed` at the last target kill this level, 0 before the first one.
+    pub last_kill: f32,
+    /// Targets killed in a row without a mistake this run.
+    pub streak: u32,
+    /// Targets killed this run.
+    pub kills: u32,
+}
+
+impl GameCore {
+    /// Initializes the game core with the given seed and tuning values, generating
+    /// characters out of `part_counts` variants of each trait category.
+    pub fn init(seed: u64, tuning: &Rc<Tuning>, part_counts: Vec<usize>) -> GameCore {
+        GameCore {
+            level: Level::init(seed, tuning, part_counts),
+            score: [0.0, 0.0],
+            game_over: false,
+            seed,
+            tuning: Rc::clone(tuning),
+            difficulty: Difficulty::Normal,
+            mode: GameMode::Classic,
+            elapsed: 0.0,
+            level_elapsed: 0.0,
+            last_kill: 0.0,
+            streak: 0,
+            kills: 0,
+        }
+    }
+
+    /// Resets the score and the game over flag, and reseeds the level for a new run.
+    ///
+    /// The same seed always generates the same sequence of levels.
+    pub fn reset(&mut self, seed: u64) {
+        self.score = [0.0, 0.0];
+        self.game_over = false;
+        self.seed = seed;
+        self.elapsed = 0.0;
+        self.streak = 0;
+        self.kills = 0;
+        self.level.rng = Rng::new(seed);
+
+        if self.mode == GameMode::TimeAttack {
+            self.level.timer = self.tuning.time_attack.clock;
+        }
+    }
+
+    /// Returns the number of kills per minute of timer running time this run.
+    pub fn kills_per_minute(&self) -> f32 {
+        if self.elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.kills as f32 / (self.elapsed / 60.0)
+    }
+
+    /// Generates the next level based on the current score.
+    pub fn set_level(&mut self) {
+        self.game_over = false;
+        self.level_elapsed = 0.0;
+        self.last_kill = 0.0;
+
+        let level = self.score[0];
+        let preset = self.tuning.preset(self.difficulty);
+        let num_chars = self.tuning.crowd_size_at(level, preset);
+        let num_targets = self.tuning.targets_at(level, self.mode);
+
+        // Spawn characters inside the game ground area
+        let x_min = GAME_WIDTH - GROUND_WIDTH - 40.0;
+        let x_max = GAME_WIDTH - CHAR_WIDTH - 50.0;
+        let y_min = GAME_HEIGHT - GROUND_HEIGHT - 50.0;
+        let y_max = GAME_HEIGHT - CHAR_HEIGHT - 70.0;
+
+        self.level.spawn_timer = self.tuning.first_spawn_delay.at(level) * preset.spawn_delay;
+        self.level.spawn_delay = self.tuning.spawn_delay * preset.spawn_delay;
+        self.level.walk_speed = self.tuning.wander.walk_speed.at(level);
+        // A wrong kill that doesn't end the run could kill the neighbour of a "next to" clue
+        self.level.next_to_clues = matches!(self.mode, GameMode::Classic | GameMode::Contracts);
+        let bounds = Rect::new(x_min, y_min, x_max - x_min, y_max - y_min);
+        self.level
+            .gen_crowd(num_chars, num_targets, preset.hinted_traits, bounds);
+        if self.mode == GameMode::TimeAttack {
+            // The level timer keeps running as the run-wide clock
+            self.level.time_limit = self.tuning.time_attack.clock;
+        } else {
+            // The crowd may have fit fewer targets
+            let targets = self.level.targets.len() as f32;
+            self.level.time_limit = self.tuning.level_time.at(level) * preset.level_time * targets;
+            self.level.timer = self.level.time_limit;
+        }
+        let level = &mut self.level;
+        level.rng.shuffle(&mut level.crowd);
+    }
+
+    /// Advances the game by `dt` seconds, applying the given player input.
+    ///
+    /// Returns the events that happened during the step. Once the level ends
+    /// the rest of the input is ignored.
+    pub fn step(&mut self, dt: f32, input: &[InputEvent]) -> Vec<CoreEvent> {
+        let mut events = Vec::new();
+
+        if let Some(index) = self.level.update_spawn(dt) {
+            events.push(CoreEvent::Spawned(index));
+        }
+        self.level.update_movement(dt);
+
+        if self.level.timer_on {
+            self.elapsed += dt;
+            self.level_elapsed += dt;
+        }
+
+        // The timer never runs out in practice mode
+        if self.mode != GameMode::Practice && self.level.update_timer(dt) {
+            self.game_over = true;
+            self.level.timer_on = false;
+            events.push(CoreEvent::TimeUp);
+            return events;
+        }
+
+        for event in input {
+            let (x, y, hit) = match *event {
+                InputEvent::Shoot(x, y) => (x, y, self.level.character_at(x, y)),
+                InputEvent::Select(direction) => {
+                    self.level.select(direction);
+                    continue;
+                }
+                InputEvent::ShootSelected => match self.level.selected {
+                    Some(index) if !self.level.crowd[index].dead => {
+                        let (x, y) = self.level.crowd[index].center(self.tuning.click_offset);
+                        (x, y, Some(index))
+                    }
+                    _ => continue,
+                },
+            };
+
+            if !self.level.timer_on {
+                continue; // Can't shoot until the whole crowd has spawned
+            }
+            events.push(CoreEvent::Shot(x, y));
+
+            if let Some(index) = hit {
+                self.kill(index, &mut events);
+                if !self.level.timer_on {
+                    return events; // The level is over
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Kills the character at the given crowd index, ending the level if it was the
+    /// last target, or if it was a wrong kill in classic or contracts mode.
+    ///
+    /// Pushes `TargetKilled`, and `LevelCleared` for the last one, if it was a target,
+    /// `WrongKill` otherwise.
+    fn kill(&mut self, index: usize, events: &mut Vec<CoreEvent>) {
+        let character = &mut self.level.crowd[index];
+        character.dead = true;
+
+        if character.target.is_some() {
+            self.add_score();
+            self.streak += 1;
+            self.kills += 1;
+
+            if self.mode == GameMode::TimeAttack {
+                let scale = self.level.crowd.len() as f32 / self.tuning.crowd_size.at(0.0);
+                self.level.timer += self.tuning.time_attack.kill_bonus * scale;
+            }
+            events.push(CoreEvent::TargetKilled(self.level_elapsed - self.last_kill));
+            self.last_kill = self.level_elapsed;
+
+            if self.level.targets_left() == 0 {
+                self.level.timer_on = false;
+                self.score[0] += 1.0;
+                events.push(CoreEvent::LevelCleared);
+            }
+        } else {
+            self.streak = 0;
+            if self.mode == GameMode::TimeAttack {
+                self.level.timer -= self.tuning.time_attack.wrong_kill_penalty;
+            }
+            if matches!(self.mode, GameMode::Classic | GameMode::Contracts)
+                || (self.mode == GameMode::TimeAttack && self.level.timer <= 0.0)
+            {
+                self.level.timer_on = false;
+                self.game_over = true;
+            }
+            events.push(CoreEvent::WrongKill(index));
+        }
+    }
+
+    /// Adds the score of a target kill. The level number goes up once the level is cleared.
+    fn add_score(&mut self) {
+        let score_base = self.tuning.score_base;
+        let level_bonus = (score_base / 10.0) * self.score[0];
+        // The time attack clock can go past its starting time
+        let time_left = (self.level.timer / self.level.time_limit).min(1.0);
+        let time_bonus = (score_base + level_bonus) * time_left;
+        let multiplier = self.tuning.preset(self.difficulty).score;
+        self.score[1] += (score_base + level_bonus + time_bonus) * multiplier;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::game::asset_bundle::Manifest;
+
+    /// Returns the number of variants of each trait category in the asset manifest.
+    pub fn part_counts() -> Vec<usize> {
+        let ron = std::fs::read_to_string("assets/manifest.ron").unwrap();
+        let manifest = Manifest::from_ron(&ron).unwrap();
+        manifest.traits.iter().map(|t| t.variant_count()).collect()
+    }
+
+    /// Returns a game core at the start of a run with the given seed, its first level set.
+    pub fn start(seed: u64, mode: GameMode) -> GameCore {
+        let mut core = GameCore::init(seed, &Rc::new(Tuning::default()), part_counts());
+        core.mode = mode;
+        core.reset(seed);
+        core.set_level();
+        core
+    }
+
+    /// Steps until the whole crowd has spawned, returning the events.
+    pub fn spawn_crowd(core: &mut GameCore) -> Vec<CoreEvent> {
+        let mut events = Vec::new();
+        while !core.level.timer_on {
+            events.extend(core.step(0.1, &[]));
+        }
+        events
+    }
+
+    /// Shoots the character at the given crowd index.
+    pub fn shoot(core: &mut GameCore, index: usize) -> Vec<CoreEvent> {
+        core.level.selected = Some(index);
+        core.step(0.0, &[InputEvent::ShootSelected])
+    }
+
+    /// Returns the crowd indices of the targets, or of everyone else.
+    pub fn indices(core: &GameCore, targets: bool) -> Vec<usize> {
+        (0..core.level.crowd.len())
+            .filter(|&i| core.level.crowd[i].target.is_some() == targets)
+            .collect()
+    }
+
+    #[test]
+    fn spawns_the_crowd_then_starts_the_timer() {
+        let mut core = start(1, GameMode::Classic);
+        assert!(!core.level.timer_on);
+        assert!(
+            shoot(&mut core, 0).is_empty(),
+            "can't shoot before the crowd spawned"
+        );
+
+        let events = spawn_crowd(&mut core);
+        let spawned: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match event {
+                CoreEvent::Spawned(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(spawned, (0..core.level.crowd.len()).collect::<Vec<_>>());
+        assert!(core.level.crowd.iter().all(|c| c.spawned));
+        assert!(core.level.timer <= core.level.time_limit);
+    }
+
+    #[test]
+    fn killing_every_target_clears_the_level() {
+        let mut core = start(2, GameMode::Contracts);
+        spawn_crowd(&mut core);
+
+        let targets = indices(&core, true);
+        assert_eq!(targets.len(), 2);
+        // Kill times are from the start of the level, then from the previous kill
+        core.step(1.5, &[]);
+        let first_kill = core.level_elapsed;
+        let events = shoot(&mut core, targets[0]);
+        assert_eq!(events[1..], [CoreEvent::TargetKilled(first_kill)]);
+        core.step(0.5, &[]);
+        let events = shoot(&mut core, targets[1]);
+        let time = core.level_elapsed - first_kill;
+        assert_eq!(
+            events[1..],
+            [CoreEvent::TargetKilled(time), CoreEvent::LevelCleared]
+        );
+        assert_eq!(core.score[0], 1.0);
+        assert_eq!(core.kills, 2);
+        assert!(!core.level.timer_on && !core.game_over);
+    }
+
+    #[test]
+    fn a_wrong_kill_ends_a_classic_run() {
+        let mut core = start(3, GameMode::Classic);
+        spawn_crowd(&mut core);
+        let innocent = indices(&core, false)[0];
+        let events = shoot(&mut core, innocent);
+        assert_eq!(events.last(), Some(&CoreEvent::WrongKill(innocent)));
+        assert!(core.game_over);
+
+        // But not a practice one
+        let mut core = start(3, GameMode::Practice);
+        spawn_crowd(&mut core);
+        let innocent = indices(&core, false)[0];
+        shoot(&mut core, innocent);
+        assert!(!core.game_over && core.level.timer_on);
+    }
+
+    #[test]
+    fn the_time_bonus_is_at_most_the_kill_score() {
+        let mut core = start(5, GameMode::TimeAttack);
+        spawn_crowd(&mut core);
+        core.level.timer = core.level.time_limit * 3.0;
+        let target = indices(&core, true)[0];
+        shoot(&mut core, target);
+        assert_eq!(core.score[1], core.tuning.score_base * 2.0);
+    }
+
+    #[test]
+    fn the_timer_runs_out() {
+        let mut core = start(4, GameMode::Classic);
+        spawn_crowd(&mut core);
+        assert!(core.step(core.level.time_limit, &[]).is_empty());
+        assert_eq!(core.step(0.1, &[]), vec![CoreEvent::TimeUp]);
+        assert!(core.game_over);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_levels() {
+        let levels = |seed: u64| {
+            let mut core = start(seed, GameMode::Classic);
+            let mut levels = Vec::new();
+            for _ in 0..5 {
+                let crowd: Vec<_> = (core.level.crowd.iter())
+                    .map(|c| (c.x, c.y, c.traits.clone(), c.target))
+                    .collect();
+                let clues: Vec<_> = (core.level.targets.iter())
+                    .map(|target| target.clues.clone())
+                    .collect();
+                levels.push((crowd, clues));
+
+                spawn_crowd(&mut core);
+                for target in indices(&core, true) {
+                    shoot(&mut core, target);
+                }
+                core.set_level();
+            }
+            levels
+        };
+        assert_eq!(levels(5), levels(5));
+        assert_ne!(levels(5), levels(6));
+    }
+}
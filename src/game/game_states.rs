@@ -3,46 +3,218 @@
 //! Game states logic implementation.
 
 use super::{utils::text::*, *};
-use macroquad::rand::ChooseRandom;
+
+const STATS_TOP: f32 = 190.0;
+const STATS_ROW_HEIGHT: f32 = 40.0;
+const BINDINGS_TOP: f32 = 165.0;
+const BINDINGS_ROW_HEIGHT: f32 = 23.0;
+const BINDINGS_FONT_SIZE: u16 = 22;
+const CONTROLS_SLOT_X: [f32; BINDING_SLOTS] = [700.0, 960.0];
 
 impl Game {
     /// Sets the game state to menu.
     pub fn set_menu(&mut self) {
         self.game_state = Menu;
-        self.score = [0.0, 0.0]; // Reset the score
+        self.save.save(); // Keep the stats of a run quit from the pause menu
+        self.core.reset(new_seed()); // Reset the score
+        self.playback = None;
         utils::sound::play_sound_looped(self.assets.bg_music, 1.0);
 
-        // Load the highscore from storage if it exists
-        let storage = &mut quad_storage::STORAGE.lock().unwrap();
-        if let (Some(highscore_level), Some(highscore_total)) = (
-            storage.get("highscore_level"),
-            storage.get("highscore_total"),
-        ) {
-            self.highscore = [
-                highscore_level.parse::<f32>().unwrap(),
-                highscore_total.parse::<f32>().unwrap(),
+        // Start with the last selected difficulty, mode and skin
+        self.core.difficulty = self.save.difficulty;
+        self.core.mode = self.save.mode;
+        if !self.use_skin(&self.save.skin.clone()) {
+            self.use_skin(DEFAULT_SKIN);
+        }
+        self.load_highscores();
+        self.fetch_leaderboard();
+        self.gen_menu_crowd();
+    }
+
+    /// Generates characters for the menu background.
+    fn gen_menu_crowd(&mut self) {
+        let bounds = Rect::new(0.0, 0.0, GAME_WIDTH - CHAR_WIDTH, GAME_HEIGHT - CHAR_HEIGHT);
+        self.core.level.scatter_crowd(200, bounds);
+    }
+
+    /// Draws the characters with the skin with the given name, returning `false` if
+    /// there's no such skin.
+    pub fn use_skin(&mut self, name: &str) -> bool {
+        if !self.assets.set_skin(name) {
+            return false;
+        }
+        self.core.level.part_counts = self.assets.part_counts();
+        true
+    }
+
+    /// Changes the selected skin by `step`, wrapping around, and saves it.
+    pub fn change_skin(&mut self, step: isize) {
+        let count = self.assets.skins.len() as isize;
+        let index = (self.assets.skin_index() as isize + step).rem_euclid(count) as usize;
+        let name = self.assets.skins[index].name.clone();
+        self.use_skin(&name);
+        self.gen_menu_crowd(); // The old crowd may use variants the new skin doesn't have
+
+        self.save.skin = name;
+        self.save.save();
+    }
+
+    /// Changes the selected color palette by `step`, wrapping around, and saves it.
+    pub fn change_palette(&mut self, step: isize) {
+        let palette = self.assets.palette().step(step);
+        self.assets.set_palette(palette);
+
+        self.save.palette = palette;
+        self.save.save();
+    }
+
+    /// Turns the pattern markers of the character tints on or off and saves it.
+    pub fn toggle_markers(&mut self) {
+        let markers = !self.assets.markers();
+        self.assets.set_markers(markers);
+
+        self.save.markers = markers;
+        self.save.save();
+    }
+
+    /// Switches the hints between words and pictures and saves it.
+    pub fn toggle_word_hints(&mut self) {
+        let word_hints = !self.assets.word_hints();
+        self.assets.set_word_hints(word_hints);
+
+        self.save.word_hints = word_hints;
+        self.save.save();
+    }
+
+    /// Loads the highscore table of the selected mode and difficulty from the save.
+    fn load_highscores(&mut self) {
+        self.highscores = self.save.highscores(self.core.mode, self.core.difficulty);
+    }
+
+    /// Changes the selected difficulty by `step` and saves it.
+    pub fn change_difficulty(&mut self, step: isize) {
+        self.core.difficulty = self.core.difficulty.step(step);
+        self.load_highscores();
+        self.fetch_leaderboard();
+
+        self.save.difficulty = self.core.difficulty;
+        self.save.save();
+    }
+
+    /// Changes the selected game mode by `step` and saves it.
+    pub fn change_mode(&mut self, step: isize) {
+        self.core.mode = self.core.mode.step(step);
+        self.load_highscores();
+        self.fetch_leaderboard();
+
+        self.save.mode = self.core.mode;
+        self.save.save();
+    }
+
+    /// Saves the current bindings.
+    pub fn save_bindings(&mut self) {
+        self.save.bindings = self.input.to_saved();
+        self.save.save();
+    }
+
+    /// Fetches the global top scores and the highscore's rank for the selected mode and difficulty.
+    fn fetch_leaderboard(&mut self) {
+        let (difficulty, mode) = (self.core.difficulty, self.core.mode);
+        self.leaderboard.top.clear();
+        self.leaderboard.rank = None;
+        if !mode.keeps_score() {
+            return;
+        }
+
+        self.leaderboard.fetch_top(difficulty, mode, TOP_COUNT);
+        if let Some(best) = self.highscores.best() {
+            self.leaderboard.fetch_rank(best.score, difficulty, mode);
+        }
+    }
+
+    /// Draws the local highscore table on the left side of the menu.
+    fn draw_highscores(&self) {
+        let x = 20.0;
+        let mut y = 200.0;
+        draw_text_centered("LOCAL TOP 10", x + 150.0, y, self.assets.font, 32, WHITE);
+        y += 45.0;
+
+        if self.highscores.entries.is_empty() {
+            draw_text_centered("No scores yet", x + 150.0, y, self.assets.font, 24, WHITE);
+            return;
+        }
+        for (i, entry) in self.highscores.entries.iter().enumerate() {
+            let columns = [
+                (0.0, format!("{}.", i + 1)),
+                (45.0, entry.initials.clone()),
+                (110.0, format!("{:.0}", entry.score)),
+                (195.0, format!("L{:.0}", entry.level)),
+                (245.0, format_date(entry.date)),
             ];
+            for (offset, text) in columns {
+                draw_text_ex(
+                    &text,
+                    x + offset,
+                    y,
+                    TextParams {
+                        font_size: 20,
+                        font: self.assets.font,
+                        color: WHITE,
+                        ..Default::default()
+                    },
+                );
+            }
+            y += 28.0;
         }
+    }
 
-        // Generate characters for the menu background
-        self.level.gen_crowd(
-            200,
-            0.0,
-            GAME_WIDTH - CHAR_WIDTH,
-            0.0,
-            GAME_HEIGHT - CHAR_HEIGHT,
-        );
+    /// Draws the global top scores on the right side of the menu.
+    fn draw_leaderboard(&self) {
+        let x = GAME_WIDTH - 170.0;
+        let mut y = 200.0;
+        draw_text_centered("GLOBAL TOP", x, y, self.assets.font, 32, WHITE);
+        y += 45.0;
+
+        if self.leaderboard.offline {
+            draw_text_centered("Offline", x, y, self.assets.font, 24, WHITE);
+            return;
+        } else if self.leaderboard.top.is_empty() {
+            draw_text_centered("No scores yet", x, y, self.assets.font, 24, WHITE);
+            y += 35.0;
+        }
+        for (i, entry) in self.leaderboard.top.iter().enumerate() {
+            draw_text_centered(
+                &format!("{}. {} {:.0}", i + 1, entry.name, entry.score),
+                x,
+                y,
+                self.assets.font,
+                24,
+                WHITE,
+            );
+            y += 35.0;
+        }
+        if let Some(rank) = self.leaderboard.rank {
+            draw_text_centered(
+                &format!("Your rank: #{}", rank),
+                x,
+                y + 10.0,
+                self.assets.font,
+                24,
+                WHITE,
+            );
+        }
     }
 
     /// Draws the game menu.
     pub fn menu(&mut self) {
         // Draw the characters in the background
-        for character in self.level.crowd.iter_mut() {
+        for character in self.core.level.crowd.iter_mut() {
             character.spawned = true;
-            character.draw(false);
+            character.draw(&self.assets);
         }
 
-        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, OVERLAY_PURPLE);
+        let overlay = self.assets.palette().theme().overlay;
+        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, overlay);
 
         draw_texture_ex(
             self.assets.logo,
@@ -55,20 +227,45 @@ impl Game {
             },
         );
 
-        // Draw the highscore
-        if self.highscore[0] > 0.0 {
-            draw_text_centered(
-                &format!("- HIGHSCORE: {:.0} -", self.highscore[1]),
-                GAME_WIDTH / 2.0,
-                GAME_HEIGHT / 2.2,
-                self.assets.font,
-                32,
-                WHITE,
-            );
+        if self.core.mode.keeps_score() {
+            self.draw_highscores();
+            self.draw_leaderboard();
         }
 
+        // Draw the mode and difficulty selectors
+        draw_text_centered(
+            &format!("^ {} v", self.core.mode.name()),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.6 - 45.0,
+            self.assets.font,
+            32,
+            WHITE,
+        );
+        let difficulty = self.core.difficulty;
+        draw_text_centered(
+            &format!(
+                "{} {} {}",
+                if difficulty == Difficulty::ALL[0] {
+                    " "
+                } else {
+                    "<"
+                },
+                difficulty.name(),
+                if difficulty == Difficulty::ALL[2] {
+                    " "
+                } else {
+                    ">"
+                },
+            ),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.6,
+            self.assets.font,
+            32,
+            WHITE,
+        );
+
         draw_blinking_text(
-            "Press Enter or click to start",
+            &format!("Press {} to start", self.input.label(Action::Confirm)),
             GAME_WIDTH / 2.0,
             GAME_HEIGHT / 1.3,
             self.assets.font,
@@ -76,68 +273,187 @@ impl Game {
             WHITE,
             1.5,
         );
+        let has_skins = self.assets.skins.len() > 1;
+        let mut settings = format!(
+            "Palette: {}   Markers: {}   Hints: {}",
+            self.assets.palette().name(),
+            if self.assets.markers() { "on" } else { "off" },
+            if self.assets.word_hints() {
+                "words"
+            } else {
+                "pictures"
+            }
+        );
+        if has_skins {
+            settings = format!("Skin: {}   {}", self.assets.skin().name, settings);
+        }
+        draw_text_centered(
+            &settings,
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.6 + 45.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+        draw_text_centered(
+            &format!(
+                "{} to change controls, {} to view stats",
+                self.input.label(Action::Controls),
+                self.input.label(Action::Stats)
+            ),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.2,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+        draw_text_centered(
+            &format!(
+                "{} to change palette, {} to toggle markers, {} to switch hints{}",
+                self.input.label(Action::ChangePalette),
+                self.input.label(Action::ToggleMarkers),
+                self.input.label(Action::SwitchHints),
+                if has_skins {
+                    format!(", {} to change skin", self.input.label(Action::ChangeSkin))
+                } else {
+                    String::new()
+                }
+            ),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.2 + 30.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
     }
 
     /// Sets the game up for playing.
     pub fn set_level(&mut self) {
         self.game_state = Playing;
-        self.game_over = false;
         set_sound_volume(self.assets.bg_music, 0.25);
+        self.core.set_level();
+    }
 
-        // Spawn 3 characters at first and add 1 for every 5 levels (max of 10)
-        let mut num_chars = 3 + (self.score[0] / 5.0) as usize;
-        if num_chars > 10 {
-            num_chars = 10;
+    /// Draws and updates the game while playing.
+    pub async fn playing(&mut self) {
+        // Take the input from the replay when playing one back
+        let (dt, input) = match self.playback.as_mut() {
+            Some(playback) => match playback.next_frame() {
+                Some(frame) => (frame.dt, frame.input.clone()),
+                None => {
+                    // The replay ended before the run did
+                    self.core.game_over = true;
+                    self.transition_level().await;
+                    return;
+                }
+            },
+            None => (get_frame_time(), self.read_input()),
+        };
+        self.recording.push_frame(dt, &input);
+        let events = self.core.step(dt, &input);
+
+        if self.core.mode == GameMode::Practice && self.input.is_pressed(Action::Reveal) {
+            self.reveal = !self.reveal;
         }
 
-        // Spawn characters inside the game ground area
-        let x_min = GAME_WIDTH - GROUND_WIDTH - 40.0;
-        let x_max = GAME_WIDTH - CHAR_WIDTH - 50.0;
-        let y_min = GAME_HEIGHT - GROUND_HEIGHT - 50.0;
-        let y_max = GAME_HEIGHT - CHAR_HEIGHT - 70.0;
+        if self.playback.is_none() && self.core.mode.keeps_score() {
+            for &event in &events {
+                self.save
+                    .stats
+                    .record(event, &self.core, &self.assets.traits);
+            }
+            self.achievements.observe(
+                &events,
+                &self.core,
+                &self.save.stats,
+                &mut self.save.achievements,
+            );
+        }
 
-        // First spawn delay, starts at 1.0s and slowly decreases to 0.1s as the player progresses
-        self.level.spawn_timer = 1.0 - (self.score[0] / 20.0);
-        if self.level.spawn_timer < 0.1 {
-            self.level.spawn_timer = 0.1;
+        let mut level_over = false;
+        for event in events {
+            match event {
+                CoreEvent::Spawned(index) => {
+                    let character = &self.core.level.crowd[index];
+                    self.smoke_fx.spawn(vec2(
+                        character.x + CHAR_WIDTH / 2.0,
+                        character.y + CHAR_HEIGHT - 30.0,
+                    ));
+                    play_sound_once(self.assets.spawn_sound);
+                }
+                CoreEvent::Shot(x, y) => {
+                    // Trigger bullet particle effect
+                    self.bullet_pos = vec2(x, y);
+                    self.bullet_fx.config.emitting = true;
+                    play_sound_once(self.assets.shoot_sound);
+                }
+                CoreEvent::LevelCleared | CoreEvent::TimeUp => {
+                    level_over = true;
+                }
+                CoreEvent::TargetKilled(_) => {}
+                CoreEvent::WrongKill(_) => {
+                    level_over = self.core.game_over;
+                }
+            }
         }
 
-        self.level.timer = LEVEL_TIME;
-        self.level.hints_color = rand_color();
-        self.level.gen_crowd(num_chars, x_min, x_max, y_min, y_max);
-        self.level.crowd.shuffle();
+        // Draw the level
+        self.draw_level();
+
+        if level_over {
+            self.transition_level().await;
+        }
     }
 
-    /// Draws and updates the game while playing.
-    pub async fn playing(&mut self) {
-        // Draw the level
-        self.game_over = self.level.draw(self.score);
-        self.bullet_fx.draw(self.renderer.mouse_position().into());
+    /// Draws the level along with its particle effects.
+    fn draw_level(&mut self) {
+        self.core.level.draw(&self.assets, self.core.score);
+        self.smoke_fx.draw();
+        self.bullet_fx.draw(self.bullet_pos);
 
-        // Check if the player clicked on the target or another character
-        if let Some(target_found) = self.check_target_click() {
-            if target_found {
-                self.add_score();
-            } else {
-                self.game_over = true;
+        if self.core.mode == GameMode::Practice {
+            if self.reveal {
+                self.core.level.draw_reveal(&self.assets);
             }
-            self.transition_level().await;
-            return;
+            draw_text_ex(
+                &format!(
+                    "PRACTICE - Press {} to reveal the {}",
+                    self.input.label(Action::Reveal),
+                    if self.core.level.targets.len() > 1 {
+                        "targets"
+                    } else {
+                        "target"
+                    }
+                ),
+                GAME_WIDTH - GROUND_WIDTH - 50.0,
+                60.0,
+                TextParams {
+                    font: self.assets.font,
+                    font_size: 24,
+                    color: WHITE,
+                    ..Default::default()
+                },
+            );
         }
 
-        if self.game_over {
-            self.transition_level().await;
+        if self.playback.is_some() {
+            draw_blinking_text(
+                "REPLAY",
+                GAME_WIDTH - 120.0,
+                70.0,
+                self.assets.font,
+                32,
+                WHITE,
+                1.5,
+            );
         }
     }
 
     /// Transitions to either the next level or the game over screen.
     pub async fn transition_level(&mut self) {
-        self.level.timer_on = false;
-
-        if !self.game_over {
+        if !self.core.game_over {
             crate::draw_game_screen_for!(self, 0.5, {
-                self.level.draw(self.score);
-                self.bullet_fx.draw(self.renderer.mouse_position().into());
+                self.draw_level();
             });
 
             self.set_level();
@@ -146,11 +462,17 @@ impl Game {
             set_sound_volume(self.assets.bg_music, 0.0);
 
             draw_game_screen_for!(self, 3.0, {
-                self.level.draw(self.score);
-                self.bullet_fx.draw(self.renderer.mouse_position().into());
-                self.level.get_target().blink();
+                self.draw_level();
+                for target in self.core.level.target_characters() {
+                    if !target.dead {
+                        target.blink();
+                    }
+                }
             });
-            self.level.get_target().spawned = true; // Make sure the target is drawn after blinking
+            // Make sure the targets are drawn after blinking
+            for target in self.core.level.target_characters() {
+                target.spawned = true;
+            }
 
             self.set_game_over();
         }
@@ -159,24 +481,150 @@ impl Game {
     /// Sets the game state to game over.
     pub fn set_game_over(&mut self) {
         self.game_state = GameOver;
+        self.recording.score = self.core.score;
+        self.reveal = false;
+        self.new_rank = None;
+
+        if let Some(playback) = &self.playback {
+            println!(
+                "Replay finished with score {:.0} (recorded {:.0})",
+                self.core.score[1], playback.replay.score[1]
+            );
+            return; // Don't save anything when playing back a replay
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        match self.recording.save() {
+            Ok(path) => println!("Replay saved to {}", path),
+            Err(e) => eprintln!("Failed to save replay: {}", e),
+        }
+
+        if !self.core.mode.keeps_score() {
+            return;
+        }
+        self.save.save(); // Save the stats
+        self.new_rank = self.highscores.rank_of(self.core.score[1]);
+        if self.new_rank.is_some() {
+            self.initials = InitialsEntry::new(&self.save.initials);
+            while get_char_pressed().is_some() {} // Drop the keys typed while playing
+            self.game_state = EnterInitials;
+        } else {
+            self.submit_score(InitialsEntry::new(&self.save.initials).text());
+        }
+    }
+
+    /// Adds the run to the highscore table under the entered initials and shows the game over screen.
+    pub fn save_initials(&mut self) {
+        self.save.initials = self.initials.text();
+        self.highscores.insert(HighscoreEntry {
+            score: self.core.score[1],
+            level: self.core.score[0],
+            date: macroquad::miniquad::date::now(),
+            mode: self.core.mode,
+            initials: self.initials.text(),
+        });
+        self.save.set_highscores(
+            self.core.mode,
+            self.core.difficulty,
+            self.highscores.clone(),
+        );
+        self.save.save();
+        self.submit_score(self.initials.text());
+        self.game_state = GameOver;
+    }
 
-        let storage = &mut quad_storage::STORAGE.lock().unwrap();
-        if let Some(highscore) = storage.get("highscore_total") {
-            if self.score[1] < highscore.parse::<f32>().unwrap() {
-                return; // Don't save the highscore if it's lower than the current one
+    /// Submits the run to the global leaderboard.
+    fn submit_score(&mut self, name: String) {
+        self.leaderboard.submit(&ScoreEntry {
+            name,
+            score: self.core.score[1],
+            level: self.core.score[0],
+            difficulty: self.core.difficulty.name().to_string(),
+            mode: self.core.mode.name().to_string(),
+        });
+    }
+
+    /// Draws the initials entry screen.
+    pub fn enter_initials(&mut self) {
+        self.core.level.draw(&self.assets, self.core.score); // Keep showing the level behind the overlay
+
+        let overlay = self.assets.palette().theme().overlay;
+        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, overlay);
+
+        let title = match self.new_rank {
+            Some(1) => "NEW HIGHSCORE!".to_string(),
+            Some(rank) => format!("YOU RANKED #{}", rank),
+            None => String::new(),
+        };
+        draw_text_centered(
+            &title,
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 2.0 - 160.0,
+            self.assets.font,
+            64,
+            WHITE,
+        );
+        draw_text_centered(
+            &format!("{:.0}", self.core.score[1]),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 2.0 - 100.0,
+            self.assets.font,
+            32,
+            WHITE,
+        );
+        draw_text_centered(
+            "Enter your initials",
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 2.0 - 30.0,
+            self.assets.font,
+            32,
+            WHITE,
+        );
+
+        // Draw the letters, with the one being changed underlined
+        let spacing = 80.0;
+        let first_x = GAME_WIDTH / 2.0 - spacing * (INITIALS_LEN as f32 - 1.0) / 2.0;
+        for (i, letter) in self.initials.letters.iter().enumerate() {
+            let x = first_x + spacing * i as f32;
+            let y = GAME_HEIGHT / 2.0 + 70.0;
+            draw_text_centered(
+                &(*letter as char).to_string(),
+                x,
+                y,
+                self.assets.font,
+                80,
+                WHITE,
+            );
+            if i == self.initials.cursor && get_time() % 1.0 < 0.5 {
+                draw_rectangle(x - 25.0, y + 15.0, 50.0, 6.0, WHITE);
             }
         }
-        // Save the highscore to storage if it's higher than the current one (or if there isn't one yet)
-        storage.set("highscore_level", &self.score[0].to_string());
-        storage.set("highscore_total", &self.score[1].to_string());
-        self.highscore = self.score;
+
+        draw_text_centered(
+            "Type the letters or use the arrows",
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.3,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+        draw_blinking_text(
+            &format!("Press {} to save", self.input.label(Action::Confirm)),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT / 1.2,
+            self.assets.font,
+            32,
+            WHITE,
+            1.5,
+        );
     }
 
     /// Draws the game over screen.
     pub fn game_over(&mut self) {
-        self.level.draw(self.score); // Keep showing the level behind the overlay
+        self.core.level.draw(&self.assets, self.core.score); // Keep showing the level behind the overlay
 
-        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, OVERLAY_PURPLE);
+        let overlay = self.assets.palette().theme().overlay;
+        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, overlay);
 
         draw_text_centered(
             "Game Over",
@@ -188,28 +636,52 @@ impl Game {
         );
 
         // Draw the highscore
-        if self.score[1] >= self.highscore[1] && self.highscore[0] > 0.0 {
+        let highscore = match (self.new_rank, self.highscores.best()) {
+            (Some(1), _) => Some("- NEW HIGHSCORE! -".to_string()),
+            (Some(rank), _) => Some(format!("- #{} ON THE HIGHSCORE TABLE -", rank)),
+            (None, Some(best)) => Some(format!("- HIGHSCORE: {:.0} -", best.score)),
+            (None, None) => None,
+        };
+        if let Some(highscore) = highscore.filter(|_| self.core.mode.keeps_score()) {
             draw_text_centered(
-                "- NEW HIGHSCORE! -",
+                &highscore,
                 GAME_WIDTH / 2.0,
                 GAME_HEIGHT / 2.2,
                 self.assets.font,
                 32,
                 WHITE,
             );
-        } else if self.highscore[0] > 0.0 {
+        }
+
+        if self.core.mode == GameMode::TimeAttack {
             draw_text_centered(
-                &format!("- HIGHSCORE: {:.0} -", self.highscore[1]),
+                &format!("Kills per minute: {:.1}", self.core.kills_per_minute()),
                 GAME_WIDTH / 2.0,
-                GAME_HEIGHT / 2.2,
+                GAME_HEIGHT / 1.8,
                 self.assets.font,
                 32,
                 WHITE,
             );
         }
 
+        if self.core.mode.keeps_score() && self.playback.is_none() {
+            let rank = match self.leaderboard.submitted_rank {
+                Some(rank) => format!("Global rank: #{}", rank),
+                None if self.leaderboard.offline => "Leaderboard offline".to_string(),
+                None => "Submitting score...".to_string(),
+            };
+            draw_text_centered(
+                &rank,
+                GAME_WIDTH / 2.0,
+                GAME_HEIGHT / 1.55,
+                self.assets.font,
+                24,
+                WHITE,
+            );
+        }
+
         draw_blinking_text(
-            "Press Enter or click to restart",
+            &format!("Press {} to restart", self.input.label(Action::Restart)),
             GAME_WIDTH / 2.0,
             GAME_HEIGHT / 1.3,
             self.assets.font,
@@ -218,7 +690,7 @@ impl Game {
             1.5,
         );
         draw_blinking_text(
-            "Press Esc to quit",
+            &format!("Press {} to quit", self.input.label(Action::Back)),
             GAME_WIDTH / 2.0,
             GAME_HEIGHT / 1.2,
             self.assets.font,
@@ -230,8 +702,6 @@ impl Game {
 
     /// Draws the game paused screen.
     pub fn paused(&mut self) {
-        self.level.timer_on = false;
-
         draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, BG_PURPLE);
 
         draw_text_centered(
@@ -244,7 +714,7 @@ impl Game {
         );
 
         draw_blinking_text(
-            "Press Enter or click to resume",
+            &format!("Press {} to resume", self.input.label(Action::Confirm)),
             GAME_WIDTH / 2.0,
             GAME_HEIGHT / 2.0 + 50.0,
             self.assets.font,
@@ -253,7 +723,7 @@ impl Game {
             1.5,
         );
         draw_blinking_text(
-            "Press Esc to quit",
+            &format!("Press {} to quit", self.input.label(Action::Back)),
             GAME_WIDTH / 2.0,
             GAME_HEIGHT / 2.0 + 100.0,
             self.assets.font,
@@ -277,7 +747,7 @@ impl Game {
             );
 
             draw_blinking_text(
-                "Press Enter or click to confirm",
+                &format!("Press {} to confirm", self.input.label(Action::Confirm)),
                 GAME_WIDTH / 2.0,
                 GAME_HEIGHT / 2.0 + 50.0,
                 self.assets.font,
@@ -286,7 +756,7 @@ impl Game {
                 1.5,
             );
             draw_blinking_text(
-                "Press Esc to cancel",
+                &format!("Press {} to cancel", self.input.label(Action::Back)),
                 GAME_WIDTH / 2.0,
                 GAME_HEIGHT / 2.0 + 100.0,
                 self.assets.font,
@@ -295,11 +765,247 @@ impl Game {
                 1.5,
             );
 
-            if is_key_pressed(KeyCode::Enter) || is_mouse_button_pressed(MouseButton::Left) {
+            if self.input.is_pressed(Action::Confirm) {
                 std::process::exit(0);
-            } else if is_key_pressed(KeyCode::Escape) {
+            } else if self.input.is_pressed(Action::Back) {
                 break;
             }
         });
     }
+
+    /// Sets the game state to the controls screen.
+    pub fn set_controls(&mut self) {
+        self.game_state = Controls;
+        self.controls_cursor = [0, 0];
+        self.rebinding = false;
+        self.rebind_error = None;
+    }
+
+    /// Draws the controls screen.
+    pub fn controls(&mut self) {
+        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, BG_PURPLE);
+
+        draw_text_centered(
+            "Controls",
+            GAME_WIDTH / 2.0,
+            120.0,
+            self.assets.font,
+            80,
+            WHITE,
+        );
+
+        for (row, action) in Action::ALL.iter().enumerate() {
+            let y = BINDINGS_TOP + row as f32 * BINDINGS_ROW_HEIGHT;
+            draw_text_centered(
+                action.name(),
+                320.0,
+                y,
+                self.assets.font,
+                BINDINGS_FONT_SIZE,
+                WHITE,
+            );
+
+            for (slot, binding) in self.input.get(*action).iter().enumerate() {
+                let selected = self.controls_cursor == [row, slot];
+                let text = if selected && self.rebinding {
+                    "Press a key..."
+                } else {
+                    binding.map_or("-", |b| b.name())
+                };
+                let color = if selected { COLOR_YELLOW } else { WHITE };
+                draw_text_centered(
+                    text,
+                    CONTROLS_SLOT_X[slot],
+                    y,
+                    self.assets.font,
+                    BINDINGS_FONT_SIZE,
+                    color,
+                );
+            }
+        }
+
+        if let Some(error) = self.rebind_error.as_ref().filter(|_| !self.rebinding) {
+            draw_text_centered(
+                error,
+                GAME_WIDTH / 2.0,
+                GAME_HEIGHT - 130.0,
+                self.assets.font,
+                32,
+                COLOR_YELLOW,
+            );
+        } else {
+            let prompt = if self.rebinding {
+                "Press Esc to cancel".to_string()
+            } else {
+                format!("Press {} to rebind", self.input.label(Action::Confirm))
+            };
+            draw_blinking_text(
+                &prompt,
+                GAME_WIDTH / 2.0,
+                GAME_HEIGHT - 130.0,
+                self.assets.font,
+                32,
+                WHITE,
+                1.5,
+            );
+        }
+        draw_text_centered(
+            &format!(
+                "Press {} to reset to defaults",
+                self.input.label(Action::Erase)
+            ),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT - 85.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+        draw_text_centered(
+            &format!("Press {} to go back", self.input.label(Action::Back)),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT - 50.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+    }
+
+    /// Moves the controls screen cursor with the select actions or by clicking a binding.
+    ///
+    /// Returns `true` if a click did anything but click the selected binding again, so
+    /// a click only rebinds what's already selected.
+    pub fn move_controls_cursor(&mut self) -> bool {
+        let [mut row, mut slot] = self.controls_cursor;
+        let mut clicked = false;
+
+        if self.input.is_pressed(Action::SelectUp) && row > 0 {
+            row -= 1;
+        } else if self.input.is_pressed(Action::SelectDown) && row < Action::ALL.len() - 1 {
+            row += 1;
+        } else if self.input.is_pressed(Action::SelectLeft) && slot > 0 {
+            slot -= 1;
+        } else if self.input.is_pressed(Action::SelectRight) && slot < BINDING_SLOTS - 1 {
+            slot += 1;
+        } else if is_mouse_button_pressed(MouseButton::Left) {
+            // Select the binding under the mouse
+            clicked = true;
+            let (mouse_x, mouse_y) = self.renderer.mouse_position();
+            for (r, s) in
+                (0..Action::ALL.len()).flat_map(|r| (0..BINDING_SLOTS).map(move |s| (r, s)))
+            {
+                let y = BINDINGS_TOP + r as f32 * BINDINGS_ROW_HEIGHT;
+                if (mouse_x - CONTROLS_SLOT_X[s]).abs() < 120.0
+                    && mouse_y > y - BINDINGS_ROW_HEIGHT / 2.0 - 7.0
+                    && mouse_y <= y + BINDINGS_ROW_HEIGHT / 2.0 - 7.0
+                {
+                    clicked = [r, s] != self.controls_cursor;
+                    (row, slot) = (r, s);
+                }
+            }
+        }
+        if [row, slot] != self.controls_cursor {
+            self.rebind_error = None;
+        }
+        self.controls_cursor = [row, slot];
+        clicked
+    }
+
+    /// Draws the toast of the last unlocked achievement on top of the screen.
+    pub fn draw_toast(&mut self) {
+        let Some((achievement, time_left)) = self.achievements.update_toast(get_frame_time())
+        else {
+            return;
+        };
+
+        // Slide in from the top and back out
+        let (width, height) = (460.0, 70.0);
+        let slide = ((TOAST_TIME - time_left).min(time_left) / 0.3).min(1.0);
+        let (x, y) = (
+            GAME_WIDTH / 2.0 - width / 2.0,
+            -height + slide * (height + 10.0),
+        );
+
+        draw_rectangle(x, y, width, height, BG_PURPLE);
+        draw_rectangle_lines(x, y, width, height, 4.0, COLOR_YELLOW);
+        draw_text_centered(
+            &format!("Achievement: {}", achievement.name),
+            GAME_WIDTH / 2.0,
+            y + 30.0,
+            self.assets.font,
+            24,
+            COLOR_YELLOW,
+        );
+        draw_text_centered(
+            &achievement.description,
+            GAME_WIDTH / 2.0,
+            y + 56.0,
+            self.assets.font,
+            20,
+            WHITE,
+        );
+    }
+
+    /// Draws the stats screen.
+    pub fn stats(&mut self) {
+        draw_rectangle(0.0, 0.0, GAME_WIDTH, GAME_HEIGHT, BG_PURPLE);
+
+        draw_text_centered(
+            "Stats",
+            GAME_WIDTH / 2.0,
+            120.0,
+            self.assets.font,
+            80,
+            WHITE,
+        );
+
+        let stats = &self.save.stats;
+        let worst_trait = match stats.worst_trait() {
+            Some((name, count)) => format!("{} ({})", name, count),
+            None => "-".to_string(),
+        };
+        let rows = [
+            ("Runs played", stats.runs.to_string()),
+            ("Targets killed", stats.kills.to_string()),
+            ("Wrong kills", stats.wrong_kills.to_string()),
+            ("Timeouts", stats.timeouts.to_string()),
+            ("Shots fired", stats.shots.to_string()),
+            ("Accuracy", format!("{:.0}%", stats.accuracy() * 100.0)),
+            (
+                "Average time to kill",
+                format!("{:.1}s", stats.average_kill_time()),
+            ),
+            ("Best streak", stats.best_streak.to_string()),
+            ("Most mistakes on", worst_trait),
+            (
+                "Achievements",
+                format!(
+                    "{}/{}",
+                    self.save.achievements.len(),
+                    self.achievements.list.len()
+                ),
+            ),
+        ];
+        for (row, (label, value)) in rows.iter().enumerate() {
+            let y = STATS_TOP + row as f32 * STATS_ROW_HEIGHT;
+            draw_text_centered(label, 450.0, y, self.assets.font, 32, WHITE);
+            draw_text_centered(value, 850.0, y, self.assets.font, 32, COLOR_YELLOW);
+        }
+
+        draw_text_centered(
+            "Practice runs and replays don't count",
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT - 85.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+        draw_text_centered(
+            &format!("Press {} to go back", self.input.label(Action::Back)),
+            GAME_WIDTH / 2.0,
+            GAME_HEIGHT - 50.0,
+            self.assets.font,
+            24,
+            WHITE,
+        );
+    }
 }
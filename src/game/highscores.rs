@@ -0,0 +1,180 @@
+//! Highscores
+//!
+//! Local top 10 table of each mode and difficulty, and the arcade style initials
+//! entry for the runs that make it to the table.
+
+use super::game_core::GameMode;
+use nanoserde::{DeJson, SerJson};
+
+/// Number of scores kept in a table.
+pub const TABLE_SIZE: usize = 10;
+/// Number of letters in the initials.
+pub const INITIALS_LEN: usize = 3;
+
+/// A run on the highscore table.
+#[derive(Clone, Debug, SerJson, DeJson)]
+pub struct HighscoreEntry {
+    pub score: f32,
+    /// The level reached.
+    pub level: f32,
+    /// When the run was played, in seconds since the Unix epoch. 0 if unknown.
+    pub date: f64,
+    pub mode: GameMode,
+    pub initials: String,
+}
+
+/// The best runs of a mode and difficulty, highest score first.
+#[derive(Clone, Debug, Default, SerJson, DeJson)]
+pub struct HighscoreTable {
+    pub entries: Vec<HighscoreEntry>,
+}
+
+impl HighscoreTable {
+    /// Returns the best run, if any.
+    pub fn best(&self) -> Option<&HighscoreEntry> {
+        self.entries.first()
+    }
+
+    /// Returns the rank (starting at 1) the score would get on the table, if it makes it.
+    pub fn rank_of(&self, score: f32) -> Option<usize> {
+        if score <= 0.0 {
+            return None;
+        }
+        let rank = 1 + self.entries.iter().filter(|e| e.score >= score).count();
+        (rank <= TABLE_SIZE).then_some(rank)
+    }
+
+    /// Adds a run to the table, dropping the lowest one if it's full.
+    pub fn insert(&mut self, entry: HighscoreEntry) {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, entry);
+        self.entries.truncate(TABLE_SIZE);
+    }
+}
+
+/// Formats a Unix time as `YYYY-MM-DD`, or `-` if unknown.
+pub fn format_date(date: f64) -> String {
+    if date <= 0.0 {
+        return "-".to_string();
+    }
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let days = (date / 86400.0) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Arcade style initials entry, one letter at a time.
+pub struct InitialsEntry {
+    pub letters: [u8; INITIALS_LEN],
+    /// Index of the letter being changed.
+    pub cursor: usize,
+}
+
+impl InitialsEntry {
+    /// Starts entering initials from the given ones, `A` for any missing or invalid letter.
+    pub fn new(initials: &str) -> InitialsEntry {
+        let mut letters = [b'A'; INITIALS_LEN];
+        for (letter, c) in letters.iter_mut().zip(initials.bytes()) {
+            if c.is_ascii_uppercase() {
+                *letter = c;
+            }
+        }
+        InitialsEntry { letters, cursor: 0 }
+    }
+
+    /// Changes the current letter by `step`, wrapping around the alphabet.
+    pub fn change_letter(&mut self, step: i8) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = b'A' + (*letter - b'A' + (26 + step % 26) as u8) % 26;
+    }
+
+    /// Moves the cursor by `step`, clamped to the letters.
+    pub fn move_cursor(&mut self, step: isize) {
+        self.cursor = (self.cursor as isize + step).clamp(0, INITIALS_LEN as isize - 1) as usize;
+    }
+
+    /// Sets the current letter to the typed character and moves to the next letter.
+    ///
+    /// Returns `false` if the character isn't a letter.
+    pub fn type_char(&mut self, c: char) -> bool {
+        if !c.is_ascii_alphabetic() {
+            return false;
+        }
+        self.letters[self.cursor] = c.to_ascii_uppercase() as u8;
+        self.move_cursor(1);
+        true
+    }
+
+    /// Returns the initials as a string.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.letters).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: f32) -> HighscoreEntry {
+        HighscoreEntry {
+            score,
+            level: 1.0,
+            date: 0.0,
+            mode: GameMode::Classic,
+            initials: "AAA".to_string(),
+        }
+    }
+
+    fn scores(table: &HighscoreTable) -> Vec<f32> {
+        table.entries.iter().map(|e| e.score).collect()
+    }
+
+    #[test]
+    fn ranks_scores_below_equal_ones() {
+        let mut table = HighscoreTable::default();
+        assert_eq!(table.rank_of(0.0), None);
+        assert_eq!(table.rank_of(10.0), Some(1));
+
+        table.entries = vec![entry(300.0), entry(200.0), entry(100.0)];
+        assert_eq!(table.rank_of(250.0), Some(2));
+        assert_eq!(table.rank_of(200.0), Some(3));
+        assert_eq!(table.rank_of(50.0), Some(4));
+    }
+
+    #[test]
+    fn a_full_table_drops_the_lowest_score() {
+        let mut table = HighscoreTable::default();
+        for score in 1..=TABLE_SIZE {
+            table.insert(entry(score as f32 * 10.0));
+        }
+        assert_eq!(scores(&table)[0], 100.0);
+        assert_eq!(table.rank_of(5.0), None);
+        assert_eq!(table.rank_of(10.0), None);
+
+        table.insert(entry(55.0));
+        assert_eq!(table.entries.len(), TABLE_SIZE);
+        assert_eq!(
+            scores(&table),
+            [100.0, 90.0, 80.0, 70.0, 60.0, 55.0, 50.0, 40.0, 30.0, 20.0]
+        );
+
+        // A tie goes below the score already there
+        let mut tied = entry(90.0);
+        tied.initials = "NEW".to_string();
+        table.insert(tied);
+        assert_eq!(table.entries[2].initials, "NEW");
+    }
+}
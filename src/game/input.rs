@@ -0,0 +1,465 @@
+//! Input
+//!
+//! Maps raw keys and mouse buttons to game actions. The bindings are saved with
+//! the rest of the save data and can be changed from the controls screen.
+
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+/// Number of bindings each action can have.
+pub const BINDING_SLOTS: usize = 2;
+
+/// The actions checked on each screen, which can't share a binding.
+const SCREENS: [&[Action]; 7] = [
+    // Menu
+    &[
+        Action::Confirm,
+        Action::Back,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectLeft,
+        Action::SelectRight,
+        Action::Controls,
+        Action::Stats,
+        Action::ChangeSkin,
+        Action::ChangePalette,
+        Action::ToggleMarkers,
+        Action::SwitchHints,
+    ],
+    // Playing
+    &[
+        Action::Pause,
+        Action::Shoot,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectLeft,
+        Action::SelectRight,
+        Action::ShootSelected,
+        Action::Reveal,
+    ],
+    // Game over
+    &[Action::Restart, Action::Back],
+    // Paused and quit confirmation
+    &[Action::Confirm, Action::Back],
+    // Stats
+    &[Action::Back, Action::Stats],
+    // Initials
+    &[
+        Action::Confirm,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectLeft,
+        Action::SelectRight,
+        Action::Erase,
+    ],
+    // Controls
+    &[
+        Action::Confirm,
+        Action::Back,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectLeft,
+        Action::SelectRight,
+        Action::Erase,
+    ],
+];
+
+/// A game action that can be bound to keys or mouse buttons.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Confirm,
+    Back,
+    Pause,
+    Shoot,
+    Restart,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    ShootSelected,
+    Reveal,
+    Controls,
+    Stats,
+    ChangeSkin,
+    ChangePalette,
+    ToggleMarkers,
+    SwitchHints,
+    Erase,
+}
+
+impl Action {
+    /// All the actions, in the order they are shown on the controls screen.
+    pub const ALL: [Action; 18] = [
+        Action::Confirm,
+        Action::Back,
+        Action::Pause,
+        Action::Shoot,
+        Action::Restart,
+        Action::SelectUp,
+        Action::SelectDown,
+        Action::SelectLeft,
+        Action::SelectRight,
+        Action::ShootSelected,
+        Action::Reveal,
+        Action::Controls,
+        Action::Stats,
+        Action::ChangeSkin,
+        Action::ChangePalette,
+        Action::ToggleMarkers,
+        Action::SwitchHints,
+        Action::Erase,
+    ];
+
+    /// Returns the action's position in `ALL`.
+    fn index(self) -> usize {
+        Action::ALL.iter().position(|a| *a == self).unwrap()
+    }
+
+    /// Returns `true` if the actions are checked on the same screen.
+    fn shares_screen(self, other: Action) -> bool {
+        SCREENS
+            .iter()
+            .any(|screen| screen.contains(&self) && screen.contains(&other))
+    }
+
+    /// Returns the action's display and storage name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Confirm => "Confirm",
+            Action::Back => "Back",
+            Action::Pause => "Pause",
+            Action::Shoot => "Shoot",
+            Action::Restart => "Restart",
+            Action::SelectUp => "Select up",
+            Action::SelectDown => "Select down",
+            Action::SelectLeft => "Select left",
+            Action::SelectRight => "Select right",
+            Action::ShootSelected => "Shoot selected",
+            Action::Reveal => "Reveal target",
+            Action::Controls => "Controls",
+            Action::Stats => "Stats",
+            Action::ChangeSkin => "Change skin",
+            Action::ChangePalette => "Change palette",
+            Action::ToggleMarkers => "Toggle markers",
+            Action::SwitchHints => "Switch hints",
+            Action::Erase => "Erase / reset",
+        }
+    }
+
+    /// Returns the default bindings of the action.
+    fn default_bindings(self) -> [Option<Binding>; BINDING_SLOTS] {
+        match self {
+            Action::Confirm => [
+                Some(Binding::Key(KeyCode::Enter)),
+                Some(Binding::Mouse(MouseButton::Left)),
+            ],
+            Action::Back => [Some(Binding::Key(KeyCode::Escape)), None],
+            Action::Pause => [Some(Binding::Key(KeyCode::Escape)), None],
+            Action::Shoot => [Some(Binding::Mouse(MouseButton::Left)), None],
+            Action::Restart => [
+                Some(Binding::Key(KeyCode::Enter)),
+                Some(Binding::Mouse(MouseButton::Left)),
+            ],
+            Action::SelectUp => [
+                Some(Binding::Key(KeyCode::Up)),
+                Some(Binding::Key(KeyCode::W)),
+            ],
+            Action::SelectDown => [
+                Some(Binding::Key(KeyCode::Down)),
+                Some(Binding::Key(KeyCode::S)),
+            ],
+            Action::SelectLeft => [
+                Some(Binding::Key(KeyCode::Left)),
+                Some(Binding::Key(KeyCode::A)),
+            ],
+            Action::SelectRight => [
+                Some(Binding::Key(KeyCode::Right)),
+                Some(Binding::Key(KeyCode::D)),
+            ],
+            Action::ShootSelected => [Some(Binding::Key(KeyCode::Space)), None],
+            Action::Reveal => [Some(Binding::Key(KeyCode::Tab)), None],
+            Action::Controls => [Some(Binding::Key(KeyCode::C)), None],
+            Action::Stats => [Some(Binding::Key(KeyCode::V)), None],
+            Action::ChangeSkin => [Some(Binding::Key(KeyCode::K)), None],
+            Action::ChangePalette => [Some(Binding::Key(KeyCode::P)), None],
+            Action::ToggleMarkers => [Some(Binding::Key(KeyCode::M)), None],
+            Action::SwitchHints => [Some(Binding::Key(KeyCode::H)), None],
+            Action::Erase => [Some(Binding::Key(KeyCode::Backspace)), None],
+        }
+    }
+}
+
+/// A key or mouse button an action is bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+/// Keys that can be bound, with their names.
+///
+/// Esc is only listed for the default bindings, it cancels rebinding on the
+/// controls screen so it can't be bound from there.
+const KEY_NAMES: [(KeyCode, &str); 66] = [
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Escape, "Esc"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::LeftShift, "LShift"),
+    (KeyCode::RightShift, "RShift"),
+    (KeyCode::LeftControl, "LCtrl"),
+    (KeyCode::RightControl, "RCtrl"),
+    (KeyCode::LeftAlt, "LAlt"),
+    (KeyCode::RightAlt, "RAlt"),
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Comma, ","),
+    (KeyCode::Period, "."),
+    (KeyCode::Slash, "/"),
+    (KeyCode::Semicolon, ";"),
+    (KeyCode::Apostrophe, "'"),
+    (KeyCode::Kp0, "Num0"),
+    (KeyCode::Kp1, "Num1"),
+    (KeyCode::Kp2, "Num2"),
+    (KeyCode::Kp3, "Num3"),
+    (KeyCode::Kp4, "Num4"),
+    (KeyCode::Kp5, "Num5"),
+    (KeyCode::Kp6, "Num6"),
+    (KeyCode::Kp7, "Num7"),
+    (KeyCode::Kp8, "Num8"),
+    (KeyCode::Kp9, "Num9"),
+];
+
+/// Key that cancels rebinding on the controls screen.
+pub const CANCEL_KEY: KeyCode = KeyCode::Escape;
+
+/// Mouse buttons that can be bound, with their names.
+const MOUSE_NAMES: [(MouseButton, &str); 3] = [
+    (MouseButton::Left, "click"),
+    (MouseButton::Right, "right click"),
+    (MouseButton::Middle, "middle click"),
+];
+
+impl Binding {
+    /// Returns the binding's display and storage name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Binding::Key(key) => KEY_NAMES
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map_or("?", |(_, name)| name),
+            Binding::Mouse(button) => MOUSE_NAMES
+                .iter()
+                .find(|(b, _)| *b == button)
+                .map_or("?", |(_, name)| name),
+        }
+    }
+
+    /// Finds the binding with the given name.
+    fn from_name(name: &str) -> Option<Binding> {
+        KEY_NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(key, _)| Binding::Key(*key))
+            .or_else(|| {
+                MOUSE_NAMES
+                    .iter()
+                    .find(|(_, n)| *n == name)
+                    .map(|(button, _)| Binding::Mouse(*button))
+            })
+    }
+
+    /// Returns the bindable key or mouse button pressed this frame, if any.
+    pub fn pressed() -> Option<Binding> {
+        if let Some(key) = get_last_key_pressed() {
+            if key != CANCEL_KEY && KEY_NAMES.iter().any(|(k, _)| *k == key) {
+                return Some(Binding::Key(key));
+            }
+        }
+        MOUSE_NAMES
+            .iter()
+            .find(|(button, _)| is_mouse_button_pressed(*button))
+            .map(|(button, _)| Binding::Mouse(*button))
+    }
+
+    /// Returns `true` if the key or mouse button was pressed this frame.
+    fn is_pressed(self) -> bool {
+        match self {
+            Binding::Key(key) => is_key_pressed(key),
+            Binding::Mouse(button) => is_mouse_button_pressed(button),
+        }
+    }
+}
+
+/// Action to key/mouse button bindings.
+pub struct InputMap {
+    bindings: [[Option<Binding>; BINDING_SLOTS]; Action::ALL.len()],
+}
+
+impl InputMap {
+    /// Returns the default bindings.
+    pub fn default_bindings() -> InputMap {
+        InputMap {
+            bindings: Action::ALL.map(Action::default_bindings),
+        }
+    }
+
+    /// Creates the bindings from the saved action name -> binding names,
+    /// falling back to the defaults for anything missing.
+    pub fn from_saved(saved: &HashMap<String, Vec<String>>) -> InputMap {
+        let mut input = InputMap::default_bindings();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            if let Some(names) = saved.get(action.name()) {
+                let mut slots = [None; BINDING_SLOTS];
+                for (slot, name) in slots.iter_mut().zip(names) {
+                    *slot = Binding::from_name(name);
+                }
+                if slots.iter().any(Option::is_some) {
+                    input.bindings[i] = slots;
+                }
+            }
+        }
+        input
+    }
+
+    /// Returns the bindings to save, action name -> binding names.
+    pub fn to_saved(&self) -> HashMap<String, Vec<String>> {
+        let mut actions = HashMap::new();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let names = self.bindings[i]
+                .iter()
+                .flatten()
+                .map(|binding| binding.name().to_string())
+                .collect();
+            actions.insert(action.name().to_string(), names);
+        }
+        actions
+    }
+
+    /// Returns `true` if any of the action's bindings was pressed this frame.
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.get(action).iter().flatten().any(|b| b.is_pressed())
+    }
+
+    /// Returns the bindings of the action.
+    pub fn get(&self, action: Action) -> [Option<Binding>; BINDING_SLOTS] {
+        self.bindings[action.index()]
+    }
+
+    /// Binds the action's `slot` to the given key or mouse button. Fails if an action
+    /// checked on the same screen, or the action's other slot, is already bound to it.
+    pub fn bind(&mut self, action: Action, slot: usize, binding: Binding) -> Result<(), String> {
+        if let Some(other) = self.bound_to(action, slot, binding) {
+            return Err(format!(
+                "{} is already bound to {}",
+                binding.name(),
+                other.name()
+            ));
+        }
+        self.bindings[action.index()][slot] = Some(binding);
+        Ok(())
+    }
+
+    /// Returns the action `binding` would conflict with if bound to the action's `slot`.
+    fn bound_to(&self, action: Action, slot: usize, binding: Binding) -> Option<Action> {
+        Action::ALL.into_iter().find(|&other| {
+            (other == action || other.shares_screen(action))
+                && (0..BINDING_SLOTS)
+                    .any(|s| (other, s) != (action, slot) && self.get(other)[s] == Some(binding))
+        })
+    }
+
+    /// Returns the action's bindings for on-screen prompts, e.g. "Enter or click".
+    pub fn label(&self, action: Action) -> String {
+        let names: Vec<&str> = self
+            .get(action)
+            .iter()
+            .flatten()
+            .map(|b| b.name())
+            .collect();
+        names.join(" or ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_bindings_dont_conflict() {
+        let input = InputMap::default_bindings();
+        for action in Action::ALL {
+            for (slot, binding) in input.get(action).into_iter().enumerate() {
+                if let Some(binding) = binding {
+                    assert_eq!(input.bound_to(action, slot, binding), None, "{:?}", action);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn binding_a_key_taken_on_the_same_screen_fails() {
+        let mut input = InputMap::default_bindings();
+        let enter = Binding::Key(KeyCode::Enter);
+        assert!(input.bind(Action::Controls, 0, enter).is_err());
+        assert!(input
+            .bind(Action::SelectUp, 1, Binding::Key(KeyCode::Up))
+            .is_err());
+        assert_eq!(
+            input.get(Action::Controls)[0],
+            Some(Binding::Key(KeyCode::C))
+        );
+
+        // Confirm isn't checked while playing, nor the pause action on the menu
+        assert!(input.bind(Action::Reveal, 0, enter).is_ok());
+        assert!(input.bind(Action::Confirm, 0, enter).is_ok());
+        assert_eq!(input.get(Action::Reveal)[0], Some(enter));
+    }
+
+    #[test]
+    fn actions_are_indexed_in_the_controls_screen_order() {
+        for (i, action) in Action::ALL.into_iter().enumerate() {
+            assert_eq!(action.index(), i);
+            assert_eq!(action as usize, i);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Leaderboard
+//!
+//! Client of the global leaderboard server (see `leaderboard-server`). Requests run
+//! on a background thread and are polled every frame with `update`, so a slow or
+//! missing server never blocks the game. Any failure just marks the leaderboard
+//! as offline and the game keeps going.
+
+use super::{game_core::GameMode, tuning::Difficulty};
+use nanoserde::{DeJson, SerJson};
+use std::sync::mpsc::{self, Receiver};
+
+/// Server used when `RUSTY_HITMAN_LEADERBOARD` isn't set.
+pub const DEFAULT_SERVER: &str = "http://127.0.0.1:8080";
+/// Number of scores shown on the menu.
+pub const TOP_COUNT: usize = 5;
+
+/// A score on the leaderboard.
+#[derive(Clone, Debug, SerJson, DeJson)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: f32,
+    pub level: f32,
+    pub difficulty: String,
+    pub mode: String,
+}
+
+/// Response of the `/scores` endpoint.
+#[derive(SerJson, DeJson)]
+struct TopResponse {
+    scores: Vec<ScoreEntry>,
+}
+
+/// Response of the `/rank` endpoint and of submitting a score.
+#[derive(SerJson, DeJson)]
+struct RankResponse {
+    rank: usize,
+}
+
+pub struct Leaderboard {
+    /// Base URL of the server.
+    server: String,
+    /// The top scores of the last fetched difficulty and mode.
+    pub top: Vec<ScoreEntry>,
+    /// The rank of the last fetched score.
+    pub rank: Option<usize>,
+    /// The rank of the last submitted score.
+    pub submitted_rank: Option<usize>,
+    /// Whether the last request failed.
+    pub offline: bool,
+    top_request: Option<Receiver<Result<String, String>>>,
+    rank_request: Option<Receiver<Result<String, String>>>,
+    submit_request: Option<Receiver<Result<String, String>>>,
+}
+
+impl Leaderboard {
+    /// Initializes the leaderboard client, reading the server from `RUSTY_HITMAN_LEADERBOARD`.
+    pub fn init() -> Leaderboard {
+        #[cfg(not(target_arch = "wasm32"))]
+        let server = std::env::var("RUSTY_HITMAN_LEADERBOARD")
+            .unwrap_or_else(|_| DEFAULT_SERVER.to_string());
+        #[cfg(target_arch = "wasm32")]
+        let server = DEFAULT_SERVER.to_string();
+
+        Leaderboard {
+            server,
+            top: Vec::new(),
+            rank: None,
+            submitted_rank: None,
+            offline: false,
+            top_request: None,
+            rank_request: None,
+            submit_request: None,
+        }
+    }
+
+    /// Submits a score. Its rank ends up in `submitted_rank`.
+    pub fn submit(&mut self, entry: &ScoreEntry) {
+        self.submitted_rank = None;
+        self.submit_request = Some(self.request("POST", "/scores", Some(entry.serialize_json())));
+    }
+
+    /// Fetches the top `count` scores of the given difficulty and mode into `top`.
+    pub fn fetch_top(&mut self, difficulty: Difficulty, mode: GameMode, count: usize) {
+        let path = format!(
+            "/scores?difficulty={}&mode={}&count={}",
+            encode(difficulty.name()),
+            encode(mode.name()),
+            count
+        );
+        self.top_request = Some(self.request("GET", &path, None));
+    }
+
+    /// Fetches the rank `score` would have in the given difficulty and mode into `rank`.
+    pub fn fetch_rank(&mut self, score: f32, difficulty: Difficulty, mode: GameMode) {
+        self.rank = None;
+        let path = format!(
+            "/rank?difficulty={}&mode={}&score={}",
+            encode(difficulty.name()),
+            encode(mode.name()),
+            score
+        );
+        self.rank_request = Some(self.request("GET", &path, None));
+    }
+
+    /// Collects the responses of the finished requests.
+    pub fn update(&mut self) {
+        if let Some(body) = self.poll(Request::Top) {
+            match TopResponse::deserialize_json(&body) {
+                Ok(response) => self.top = response.scores,
+                Err(_) => self.offline = true,
+            }
+        }
+        if let Some(body) = self.poll(Request::Rank) {
+            self.rank = RankResponse::deserialize_json(&body).ok().map(|r| r.rank);
+        }
+        if let Some(body) = self.poll(Request::Submit) {
+            self.submitted_rank = RankResponse::deserialize_json(&body).ok().map(|r| r.rank);
+        }
+    }
+
+    /// Returns the body of the given request's response if it has finished successfully.
+    fn poll(&mut self, request: Request) -> Option<String> {
+        let receiver = match request {
+            Request::Top => &mut self.top_request,
+            Request::Rank => &mut self.rank_request,
+            Request::Submit => &mut self.submit_request,
+        };
+        let result = receiver.as_ref()?.try_recv().ok()?;
+        *receiver = None;
+
+        match result {
+            Ok(body) => {
+                self.offline = false;
+                Some(body)
+            }
+            Err(e) => {
+                if !self.offline {
+                    eprintln!("Leaderboard offline: {}", e);
+                }
+                self.offline = true;
+                None
+            }
+        }
+    }
+
+    /// Sends a request to the server on a background thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<String>,
+    ) -> Receiver<Result<String, String>> {
+        let (sender, receiver) = mpsc::channel();
+        let (server, method, path) = (self.server.clone(), method.to_string(), path.to_string());
+        std::thread::spawn(move || {
+            // The receiver is gone if a newer request replaced this one
+            let _ = sender.send(http::request(&server, &method, &path, body.as_deref()));
+        });
+        receiver
+    }
+
+    /// The browser build has no leaderboard, every request fails right away.
+    #[cfg(target_arch = "wasm32")]
+    fn request(
+        &self,
+        _method: &str,
+        _path: &str,
+        _body: Option<String>,
+    ) -> Receiver<Result<String, String>> {
+        let (sender, receiver) = mpsc::channel();
+        let _ = sender.send(Err("not available in the browser".to_string()));
+        receiver
+    }
+}
+
+/// The requests that can be in flight.
+enum Request {
+    Top,
+    Rank,
+    Submit,
+}
+
+/// Percent-encodes a query string value.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Minimal blocking HTTP/1.1 client, plain `http://` only.
+#[cfg(not(target_arch = "wasm32"))]
+mod http {
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// Sends a request and returns the response body if the status is 200.
+    pub fn request(
+        server: &str,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, String> {
+        let host = server
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("unsupported server URL {}", server))?
+            .trim_end_matches('/');
+        // Plain HTTP defaults to port 80 when the URL doesn't give one
+        let has_port = host
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+        let address = if has_port {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        let address = address
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("can't resolve {}", host))?;
+
+        let mut stream =
+            TcpStream::connect_timeout(&address, TIMEOUT).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        stream
+            .set_write_timeout(Some(TIMEOUT))
+            .map_err(|e| e.to_string())?;
+
+        let body = body.unwrap_or("");
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            host,
+            body.len(),
+            body
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| e.to_string())?;
+
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .map_err(|e| e.to_string())?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or("malformed response")?;
+        let status = head.lines().next().unwrap_or_default();
+        if status.split(' ').nth(1) != Some("200") {
+            return Err(format!("server responded with {}", status));
+        }
+        Ok(body.to_string())
+    }
+}
@@ -1,77 +1,46 @@
 //! Character struct that represents a character in the crowd.
 
 use super::*;
-use macroquad_particles::*;
 
 /// Character struct that represents a character in the crowd.
 pub struct Character {
     pub x: f32,
     pub y: f32,
-    pub is_target: bool,
-    pub textures: [Texture2D; 6],
-    pub color: Color,
+    /// Index of the level target the character is, if any.
+    pub target: Option<usize>,
+    /// The variant of each trait category.
+    pub traits: Vec<usize>,
     pub spawned: bool,
     pub dead: bool,
-    smoke_fx: Emitter,
+    /// What the character is doing while wandering around.
+    pub movement: Movement,
+    /// The character's velocity in the last movement update, in pixels per second.
+    pub velocity: Vec2,
 }
 
 impl Character {
-    /// Creates a new `Character` with the given position and textures.
-    pub fn init(x: f32, y: f32, textures: [Texture2D; 6]) -> Character {
-        // Smoke particle effect when spawning.
-        let smoke_fx = Emitter::new(EmitterConfig {
-            emission_shape: EmissionShape::Sphere { radius: 50.0 },
-            one_shot: true,
-            lifetime: 0.5,
-            explosiveness: 1.0,
-            amount: 150,
-            shape: ParticleShape::Circle { subdivisions: 10 },
-            emitting: true,
-            initial_direction: vec2(0.0, -1.0),
-            initial_direction_spread: 6.0,
-            initial_velocity: 310.0,
-            initial_velocity_randomness: 0.6,
-            linear_accel: -7.5,
-            size: 10.0,
-            size_randomness: 3.0,
-            size_curve: Some(Curve {
-                points: vec![(0.005, 1.48), (0.255, 1.08), (1.0, 0.12)],
-                interpolation: Interpolation::Linear,
-                resolution: 30,
-            }),
-            blend_mode: BlendMode::Additive,
-            colors_curve: ColorCurve {
-                start: Color::new(1.0, 1.0, 1.0, 1.0),
-                mid: Color::new(0.8, 0.8, 0.8, 0.5),
-                end: Color::new(0.5, 0.5, 0.5, 0.5),
-            },
-            gravity: vec2(0.0, -500.0),
-            post_processing: Some(PostProcessing),
-            ..Default::default()
-        });
-
+    /// Creates a new `Character` with the given position and traits.
+    pub fn init(x: f32, y: f32, traits: Vec<usize>) -> Character {
         Character {
             x,
             y,
-            textures,
-            is_target: false,
-            color: rand_color(),
+            traits,
+            target: None,
             spawned: false,
             dead: false,
-            smoke_fx,
+            movement: Movement::Idle(0.0),
+            velocity: Vec2::ZERO,
         }
     }
 
     /// Draws all parts of the character.
-    ///
-    /// If `use_smoke` is true, the character will spawn with a smoke effect.
-    pub fn draw(&mut self, use_smoke: bool) {
+    pub fn draw(&self, assets: &AssetBundle) {
         if !self.spawned {
             return;
         } else if self.dead {
             // Draw blood splatter if the character is dead.
             draw_texture_ex(
-                self.textures[asset_bundle::CHAR_PARTS_COUNT],
+                assets.blood,
                 self.x,
                 self.y,
                 WHITE,
@@ -83,41 +52,42 @@ impl Character {
             return;
         }
 
-        for i in 0..asset_bundle::CHAR_PARTS_COUNT {
-            let mut y = self.y;
-            let mut color = self.color;
-
-            if i == 2 {
-                y += 10.0; // Offset the face by 10 pixels.
-            }
-            if i == 2 || i == 3 {
-                color = WHITE; // Don't colorize the face or hat.
-            }
+        let tint = assets.traits.tint(&self.traits, assets.palette());
+        for &i in assets.traits.draw_order() {
+            let category = assets.traits.get(i);
+            let (offset_x, offset_y) = category.offset;
+            let color = if category.tint { tint } else { WHITE };
 
-            draw_texture_ex(
-                self.textures[i],
-                self.x,
-                y,
+            assets.draw_char_part(
+                i,
+                self.traits[i],
+                self.x + offset_x,
+                self.y + offset_y,
+                vec2(CHAR_WIDTH, CHAR_HEIGHT),
                 color,
-                DrawTextureParams {
-                    dest_size: Some(vec2(CHAR_WIDTH, CHAR_HEIGHT)),
-                    ..Default::default()
-                },
             );
         }
 
-        if use_smoke {
-            self.smoke_fx
-                .draw(vec2(self.x + CHAR_WIDTH / 2.0, self.y + CHAR_HEIGHT - 30.0));
+        // The tint's pattern by the feet
+        if let Some(variant) = assets.traits.color_variant(&self.traits) {
+            if assets.markers() {
+                let size = 20.0;
+                let (x, y) = (self.x + CHAR_WIDTH - size, self.y + CHAR_HEIGHT - size);
+                draw_marker(variant, x, y, size, tint);
+            }
         }
     }
 
+    /// Returns the center of the character's clickable area, which starts `click_offset` below the top.
+    pub fn center(&self, click_offset: f32) -> (f32, f32) {
+        (
+            self.x + CHAR_WIDTH / 2.0,
+            self.y + (CHAR_HEIGHT + click_offset) / 2.0,
+        )
+    }
+
     /// Blinks the character on and off by setting `spawned` to true or false.
     pub fn blink(&mut self) {
-        if get_time() % 0.5 < 0.25 {
-            self.spawned = false;
-        } else {
-            self.spawned = true;
-        }
+        self.spawned = get_time() % 0.5 >= 0.25;
     }
 }
@@ -0,0 +1,611 @@
+//! Clues.
+//!
+//! What the hints say about a target: traits they have or don't have, who they stand
+//! next to, and where they are. A set of clues is only given once the solver proves
+//! the target is the one character in the crowd that fits all of them, so each target
+//! of a contract can be told apart from the others too.
+
+use super::*;
+use traits::TraitRegistry;
+
+/// Distance between two characters under which they stand next to each other, in pixels.
+const NEXT_TO_DISTANCE: f32 = CHAR_WIDTH * 1.5;
+/// How clearly a clue about where characters stand has to hold, in pixels. The target
+/// has to fit it by the margin and anyone else is only ruled out by missing it by the
+/// margin, so a near call never decides who the target is.
+const CLUE_MARGIN: f32 = CHAR_WIDTH / 4.0;
+/// Attempts at picking clues only the target fits before falling back to trait clues,
+/// and at generating traits for a character that can't be told apart.
+const PICK_ATTEMPTS: usize = 100;
+
+/// A clue about the target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clue {
+    /// Has the variant of the trait category.
+    Has(usize, usize),
+    /// Doesn't have the variant of the trait category.
+    Not(usize, usize),
+    /// Stands next to someone with the variant of the trait category.
+    NextTo(usize, usize),
+    /// Is the furthest in the direction of everyone with the variant of the trait category.
+    Extreme(Direction, usize, usize),
+    /// Stands in the half of the level in the direction.
+    Half(Direction),
+}
+
+impl Clue {
+    /// Returns `true` if the character at the given crowd index fits the clue, or misses
+    /// a clue about where characters stand by too little to be ruled out.
+    pub fn fits(&self, crowd: &[Character], index: usize, bounds: Rect) -> bool {
+        self.fits_by(crowd, index, bounds, -CLUE_MARGIN)
+    }
+
+    /// Returns `true` if the character at the given crowd index fits the clue, by the
+    /// margin for a clue about where characters stand.
+    pub fn clearly_fits(&self, crowd: &[Character], index: usize, bounds: Rect) -> bool {
+        self.fits_by(crowd, index, bounds, CLUE_MARGIN)
+    }
+
+    /// Returns `true` if the character at the given crowd index fits the clue by `margin`
+    /// pixels, negative to let near misses fit. Compares the characters' centers and
+    /// leaves the dead out.
+    fn fits_by(&self, crowd: &[Character], index: usize, bounds: Rect, margin: f32) -> bool {
+        let character = &crowd[index];
+        let pos = center(character);
+        let others = || {
+            (crowd.iter().enumerate())
+                .filter(move |(j, other)| *j != index && !other.dead)
+                .map(|(_, other)| other)
+        };
+
+        match *self {
+            Clue::Has(part, variant) => character.traits[part] == variant,
+            Clue::Not(part, variant) => character.traits[part] != variant,
+            Clue::NextTo(part, variant) => others().any(|other| {
+                other.traits[part] == variant
+                    && pos.distance(center(other)) < NEXT_TO_DISTANCE - margin
+            }),
+            Clue::Extreme(direction, part, variant) => {
+                character.traits[part] == variant
+                    && others()
+                        .filter(|other| other.traits[part] == variant)
+                        .all(|other| {
+                            let other = center(other);
+                            match direction {
+                                Direction::Up => pos.y < other.y - margin,
+                                Direction::Down => pos.y > other.y + margin,
+                                Direction::Left => pos.x < other.x - margin,
+                                Direction::Right => pos.x > other.x + margin,
+                            }
+                        })
+            }
+            Clue::Half(direction) => {
+                // The middle of the area the characters stand in, `bounds` is where
+                // their top left corners can be
+                let middle = bounds.center() + vec2(CHAR_WIDTH, CHAR_HEIGHT) / 2.0;
+                match direction {
+                    Direction::Up => pos.y < middle.y - margin,
+                    Direction::Down => pos.y > middle.y + margin,
+                    Direction::Left => pos.x < middle.x - margin,
+                    Direction::Right => pos.x > middle.x + margin,
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the clue is about where characters stand, so it only holds
+    /// while the crowd stands still.
+    pub fn is_spatial(&self) -> bool {
+        matches!(self, Clue::NextTo(..) | Clue::Extreme(..) | Clue::Half(_))
+    }
+
+    /// Returns the trait category the clue is about, if any.
+    pub fn category(&self) -> Option<usize> {
+        match *self {
+            Clue::Has(part, _)
+            | Clue::Not(part, _)
+            | Clue::NextTo(part, _)
+            | Clue::Extreme(_, part, _) => Some(part),
+            Clue::Half(_) => None,
+        }
+    }
+
+    /// Returns the text shown next to the clue's icon in the hints.
+    pub fn label(&self, traits: &TraitRegistry) -> String {
+        match *self {
+            Clue::Has(part, _) => traits.get(part).name.clone(),
+            Clue::Not(..) => "Not this".to_string(),
+            Clue::NextTo(..) => "Next to".to_string(),
+            Clue::Extreme(direction, ..) => match direction {
+                Direction::Up => "Furthest back",
+                Direction::Down => "Frontmost",
+                Direction::Left => "Leftmost",
+                Direction::Right => "Rightmost",
+            }
+            .to_string(),
+            Clue::Half(direction) => match direction {
+                Direction::Up => "Back half",
+                Direction::Down => "Front half",
+                Direction::Left => "Left half",
+                Direction::Right => "Right half",
+            }
+            .to_string(),
+        }
+    }
+
+    /// Returns what the clue says about the target in the word hints, "wears a beanie".
+    pub fn describe(&self, assets: &AssetBundle) -> String {
+        let phrase = |part: usize, variant: usize, has: bool| {
+            let name = assets.variant_name(part, variant);
+            assets.traits.get(part).describe(variant, &name, has)
+        };
+        match *self {
+            Clue::Has(part, variant) => phrase(part, variant, true),
+            Clue::Not(part, variant) => phrase(part, variant, false),
+            Clue::NextTo(part, variant) => {
+                format!("stands next to someone who {}", phrase(part, variant, true))
+            }
+            Clue::Extreme(direction, part, variant) => {
+                let extreme = match direction {
+                    Direction::Up => "furthest back",
+                    Direction::Down => "frontmost",
+                    Direction::Left => "leftmost",
+                    Direction::Right => "rightmost",
+                };
+                let who = phrase(part, variant, true);
+                format!("is the {} of everyone who {}", extreme, who)
+            }
+            Clue::Half(direction) => {
+                let half = match direction {
+                    Direction::Up => "back",
+                    Direction::Down => "front",
+                    Direction::Left => "left",
+                    Direction::Right => "right",
+                };
+                format!("stands in the {} half", half)
+            }
+        }
+    }
+
+    /// Returns the name the clue's mistakes are counted under in the stats.
+    pub fn stat_name(&self, traits: &TraitRegistry) -> String {
+        match self.category() {
+            Some(part) => traits.get(part).name.clone(),
+            None => "Position".to_string(),
+        }
+    }
+}
+
+/// Returns the center of the character.
+fn center(character: &Character) -> Vec2 {
+    vec2(character.x, character.y) + vec2(CHAR_WIDTH, CHAR_HEIGHT) / 2.0
+}
+
+/// Returns `true` if the character at the given crowd index fits all the clues.
+pub fn fits_all(clues: &[Clue], crowd: &[Character], index: usize, bounds: Rect) -> bool {
+    clues.iter().all(|clue| clue.fits(crowd, index, bounds))
+}
+
+/// Returns `true` if the character at the given crowd index is the only one that fits
+/// all the clues: it clearly fits them and everyone else clearly misses one.
+pub fn is_unique(clues: &[Clue], crowd: &[Character], index: usize, bounds: Rect) -> bool {
+    clues
+        .iter()
+        .all(|clue| clue.clearly_fits(crowd, index, bounds))
+        && !(0..crowd.len()).any(|i| i != index && fits_all(clues, crowd, i, bounds))
+}
+
+/// Returns the clues as a sentence for the word hints, "Wears a beanie, is red and
+/// stands in the left half."
+pub fn describe(clues: &[Clue], assets: &AssetBundle) -> String {
+    let mut phrases: Vec<String> = clues.iter().map(|clue| clue.describe(assets)).collect();
+    let sentence = match phrases.pop() {
+        None => return String::new(),
+        Some(last) if phrases.is_empty() => last,
+        Some(last) => format!("{} and {}", phrases.join(", "), last),
+    };
+    let mut chars = sentence.chars();
+    let first = chars.next().unwrap().to_uppercase();
+    format!("{}{}.", first, chars.as_str())
+}
+
+impl Level {
+    /// Picks `hints` clues about each of the `targets` first characters in the crowd,
+    /// which no one else fits all of, weighted by the tuning's clue weights.
+    ///
+    /// Falls back to clues about the targets' traits if no such clues are found for one
+    /// of them, see `trait_clues`, which can return clues for fewer targets.
+    pub(super) fn pick_clues(&mut self, targets: usize, hints: usize) -> Vec<Vec<Clue>> {
+        let mut picked = Vec::with_capacity(targets);
+        for target in 0..targets {
+            match self.try_pick_clues(target, hints) {
+                Some(clues) => picked.push(clues),
+                None => return self.trait_clues(targets, hints),
+            }
+        }
+        picked
+    }
+
+    /// Tries picking `hints` clues that only the target at the given crowd index fits
+    /// all of, `PICK_ATTEMPTS` times.
+    fn try_pick_clues(&mut self, target: usize, hints: usize) -> Option<Vec<Clue>> {
+        let kinds = self.candidate_clues(target);
+        let weights = &self.tuning.clues;
+        let weights = [
+            weights.has,
+            weights.not,
+            weights.next_to,
+            weights.extreme,
+            weights.half,
+        ];
+
+        for _ in 0..PICK_ATTEMPTS {
+            let mut remaining: Vec<Vec<Clue>> = kinds.clone();
+            let mut clues: Vec<Clue> = Vec::with_capacity(hints);
+            while clues.len() < hints {
+                let open: Vec<usize> = (0..remaining.len())
+                    .filter(|&k| !remaining[k].is_empty() && weights[k] > 0.0)
+                    .collect();
+                let Some(&last) = open.last() else {
+                    break; // Out of clues
+                };
+
+                // Pick a kind by weight, then one of its clues
+                let total: f32 = open.iter().map(|&k| weights[k]).sum();
+                let mut roll = self.rng.gen_range(0.0, total);
+                let kind = open
+                    .into_iter()
+                    .find(|&k| {
+                        roll -= weights[k];
+                        roll < 0.0
+                    })
+                    .unwrap_or(last);
+                let i = self.rng.gen_range(0, remaining[kind].len());
+                let clue = remaining[kind].swap_remove(i);
+
+                // One trait clue per category, "has a hat" and "not this hat" say the same
+                let trait_clue = |c: &Clue| matches!(c, Clue::Has(..) | Clue::Not(..));
+                if trait_clue(&clue)
+                    && clues
+                        .iter()
+                        .any(|c| trait_clue(c) && c.category() == clue.category())
+                {
+                    continue;
+                }
+                clues.push(clue);
+            }
+
+            if is_unique(&clues, &self.crowd, target, self.bounds) {
+                return Some(clues);
+            }
+        }
+        None
+    }
+
+    /// Picks `hints` of each target's traits as its clues. Generates new traits for the
+    /// targets that can't be told apart from the ones before them, then for the other
+    /// characters that fit all of a target's clues.
+    ///
+    /// Hints more traits, then fewer targets, when `PICK_ATTEMPTS` aren't enough, so it
+    /// can return clues for fewer than `targets` targets.
+    fn trait_clues(&mut self, targets: usize, hints: usize) -> Vec<Vec<Clue>> {
+        let all = self.part_counts.len();
+        for targets in (1..=targets).rev() {
+            for hints in hints.min(all)..=all {
+                if let Some(picked) = self.try_trait_clues(targets, hints) {
+                    return picked;
+                }
+            }
+        }
+
+        // Every trait of a single target, leaving out anyone who still has them all
+        let clues: Vec<Clue> = (0..all)
+            .map(|part| Clue::Has(part, self.crowd[0].traits[part]))
+            .collect();
+        let bounds = self.bounds;
+        let mut i = 1;
+        while i < self.crowd.len() {
+            if fits_all(&clues, &self.crowd, i, bounds) {
+                self.crowd.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        vec![clues]
+    }
+
+    /// Tries picking `hints` of each target's traits as its clues, generating new traits
+    /// up to `PICK_ATTEMPTS` times for each character that can't be told apart.
+    fn try_trait_clues(&mut self, targets: usize, hints: usize) -> Option<Vec<Vec<Clue>>> {
+        let bounds = self.bounds;
+        let mut picked: Vec<Vec<Clue>> = Vec::with_capacity(targets);
+        for target in 0..targets {
+            let mut found = None;
+            for _ in 0..PICK_ATTEMPTS {
+                let mut parts: Vec<usize> = (0..self.part_counts.len()).collect();
+                self.rng.shuffle(&mut parts);
+                parts.truncate(hints);
+                parts.sort();
+                let clues: Vec<Clue> = parts
+                    .iter()
+                    .map(|&part| Clue::Has(part, self.crowd[target].traits[part]))
+                    .collect();
+
+                // Neither fits all of the other's clues
+                let crowd = &self.crowd;
+                if picked.iter().enumerate().all(|(other, other_clues)| {
+                    !fits_all(&clues, crowd, other, bounds)
+                        && !fits_all(other_clues, crowd, target, bounds)
+                }) {
+                    found = Some(clues);
+                    break;
+                }
+                self.crowd[target].traits = self.rand_traits();
+            }
+            picked.push(found?);
+        }
+
+        for i in targets..self.crowd.len() {
+            let mut attempts = 0;
+            while picked
+                .iter()
+                .any(|clues| fits_all(clues, &self.crowd, i, bounds))
+            {
+                if attempts == PICK_ATTEMPTS {
+                    return None;
+                }
+                self.crowd[i].traits = self.rand_traits();
+                attempts += 1;
+            }
+        }
+        Some(picked)
+    }
+
+    /// Returns the index of the living target whose clues the character at the given
+    /// crowd index misses the fewest of, the first one on ties.
+    pub fn closest_target(&self, index: usize) -> usize {
+        (0..self.targets.len())
+            .filter(|&target| self.target_alive(target))
+            .min_by_key(|&target| {
+                self.targets[target]
+                    .clues
+                    .iter()
+                    .filter(|clue| !clue.fits(&self.crowd, index, self.bounds))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns the clues the target at the given crowd index fits, grouped by kind in
+    /// the order of the tuning's clue weights.
+    fn candidate_clues(&self, index: usize) -> Vec<Vec<Clue>> {
+        let (crowd, bounds) = (&self.crowd, self.bounds);
+        let target = &crowd[index];
+        let others = || {
+            (crowd.iter().enumerate())
+                .filter(move |(i, _)| *i != index)
+                .map(|(_, c)| c)
+        };
+        let categories = 0..self.part_counts.len();
+
+        let has = categories
+            .clone()
+            .map(|part| Clue::Has(part, target.traits[part]))
+            .collect();
+
+        // Only variants someone else has, the others don't rule anyone out
+        let mut not = Vec::new();
+        for part in categories.clone() {
+            for variant in 0..self.part_counts[part] {
+                if variant != target.traits[part] && crowd.iter().any(|c| c.traits[part] == variant)
+                {
+                    not.push(Clue::Not(part, variant));
+                }
+            }
+        }
+
+        // Only next to someone who isn't a target, a target is gone once they're killed
+        let mut next_to = Vec::new();
+        let near = |other: &Character| {
+            center(target).distance(center(other)) < NEXT_TO_DISTANCE - CLUE_MARGIN
+        };
+        let neighbours = others().filter(|other| other.target.is_none() && near(other));
+        for other in neighbours.filter(|_| self.next_to_clues) {
+            for part in categories.clone() {
+                let clue = Clue::NextTo(part, other.traits[part]);
+                if !next_to.contains(&clue) {
+                    next_to.push(clue);
+                }
+            }
+        }
+
+        // Only when someone else has the variant, else it's the same as having it
+        let mut extreme = Vec::new();
+        for part in categories {
+            let variant = target.traits[part];
+            if others().all(|c| c.traits[part] != variant) {
+                continue;
+            }
+            for direction in [
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                let clue = Clue::Extreme(direction, part, variant);
+                if clue.clearly_fits(crowd, index, bounds) {
+                    extreme.push(clue);
+                }
+            }
+        }
+
+        let half = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ]
+        .map(Clue::Half)
+        .into_iter()
+        .filter(|clue| clue.clearly_fits(crowd, index, bounds))
+        .collect();
+
+        vec![has, not, next_to, extreme, half]
+    }
+
+    /// Returns `true` if the crowd wanders around. It stands still when a clue about one
+    /// of the targets is about where characters stand.
+    pub fn wanders(&self) -> bool {
+        !(self.targets.iter())
+            .flat_map(|target| &target.clues)
+            .any(Clue::is_spatial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tuning::ClueWeights;
+
+    /// Where the characters' top left corners can be, the middle of their area is (260, 260).
+    const BOUNDS: Rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        w: 400.0,
+        h: 400.0,
+    };
+
+    /// A character at the given top left corner with a hat (category 0) and a color (1).
+    fn character(x: f32, hat: usize, color: usize) -> Character {
+        Character::init(x, 0.0, vec![hat, color])
+    }
+
+    #[test]
+    fn trait_clues_are_unique_to_the_target() {
+        let mut crowd = vec![character(0.0, 1, 0), character(200.0, 0, 0)];
+        let clues = [Clue::Has(0, 1)];
+        assert!(is_unique(&clues, &crowd, 0, BOUNDS));
+        assert!(!is_unique(&clues, &crowd, 1, BOUNDS));
+
+        crowd.push(character(400.0, 1, 1));
+        assert!(!is_unique(&clues, &crowd, 0, BOUNDS));
+        assert!(is_unique(
+            &[Clue::Has(0, 1), Clue::Not(1, 1)],
+            &crowd,
+            0,
+            BOUNDS
+        ));
+    }
+
+    #[test]
+    fn half_clues_need_a_margin() {
+        let clue = [Clue::Half(Direction::Left)];
+        // Centered at 270, just past the middle
+        let crowd = vec![character(0.0, 0, 0), character(210.0, 0, 0)];
+        assert!(!is_unique(&clue, &crowd, 0, BOUNDS));
+        // Centered at 360, clearly in the right half
+        let crowd = vec![character(0.0, 0, 0), character(300.0, 0, 0)];
+        assert!(is_unique(&clue, &crowd, 0, BOUNDS));
+        // The target has to be clearly in its half too
+        let crowd = vec![character(190.0, 0, 0), character(400.0, 0, 0)];
+        assert!(!is_unique(&clue, &crowd, 0, BOUNDS));
+    }
+
+    #[test]
+    fn extreme_clues_need_a_margin() {
+        let clue = [Clue::Extreme(Direction::Left, 0, 1)];
+        let crowd = vec![character(0.0, 1, 0), character(20.0, 1, 0)];
+        assert!(!is_unique(&clue, &crowd, 0, BOUNDS));
+        let crowd = vec![character(0.0, 1, 0), character(200.0, 1, 0)];
+        assert!(is_unique(&clue, &crowd, 0, BOUNDS));
+    }
+
+    #[test]
+    fn next_to_clues_need_a_margin() {
+        let clue = [Clue::NextTo(1, 1)];
+        // The first character is 100 away from the one with the color, the second 200
+        let crowd = vec![
+            character(0.0, 0, 0),
+            character(300.0, 0, 0),
+            character(100.0, 0, 1),
+        ];
+        assert!(!is_unique(&clue, &crowd, 0, BOUNDS));
+        // Now 250 away
+        let crowd = vec![
+            character(0.0, 0, 0),
+            character(350.0, 0, 0),
+            character(100.0, 0, 1),
+        ];
+        assert!(is_unique(&clue, &crowd, 0, BOUNDS));
+    }
+
+    #[test]
+    fn dead_characters_are_left_out() {
+        let clue = Clue::Extreme(Direction::Left, 0, 1);
+        let mut crowd = vec![character(0.0, 1, 0), character(300.0, 1, 0)];
+        assert!(!clue.fits(&crowd, 1, BOUNDS));
+        crowd[0].dead = true;
+        assert!(clue.fits(&crowd, 1, BOUNDS));
+
+        let clue = Clue::NextTo(0, 1);
+        assert!(!clue.fits(&crowd, 1, BOUNDS));
+    }
+
+    #[test]
+    fn trait_clues_drop_targets_that_cant_be_told_apart() {
+        // A single category of 2 variants can't tell 3 targets apart, nor 2 from anyone else
+        let tuning = Tuning {
+            clues: ClueWeights {
+                has: 1.0,
+                not: 0.0,
+                next_to: 0.0,
+                extreme: 0.0,
+                half: 0.0,
+            },
+            ..Tuning::default()
+        };
+        let mut level = Level::init(0, &Rc::new(tuning), vec![2]);
+        level.gen_crowd(5, 3, 1, BOUNDS);
+
+        assert_eq!(level.targets.len(), 1);
+        assert!(level.crowd.len() > 1);
+        let clues = &level.targets[0].clues;
+        assert!(is_unique(clues, &level.crowd, 0, BOUNDS));
+        assert!(level.crowd[1..].iter().all(|c| c.target.is_none()));
+    }
+
+    #[test]
+    fn next_to_clues_can_be_turned_off() {
+        let tuning = Tuning {
+            clues: ClueWeights {
+                has: 0.0,
+                not: 0.0,
+                next_to: 1.0,
+                extreme: 0.0,
+                half: 0.0,
+            },
+            ..Tuning::default()
+        };
+        let next_to = |enabled: bool| {
+            let mut level = Level::init(0, &Rc::new(tuning.clone()), vec![5, 5, 5]);
+            level.next_to_clues = enabled;
+            (0..20).any(|_| {
+                level.gen_crowd(8, 1, 2, BOUNDS);
+                let clues = &level.targets[0].clues;
+                clues.iter().any(|clue| matches!(clue, Clue::NextTo(..)))
+            })
+        };
+        assert!(next_to(true));
+        assert!(!next_to(false));
+    }
+
+    #[test]
+    fn a_scattered_crowd_has_no_targets() {
+        let mut level = Level::init(0, &Rc::new(Tuning::default()), vec![5, 5]);
+        level.gen_crowd(5, 1, 1, BOUNDS);
+        level.scatter_crowd(5, BOUNDS);
+        assert!(!level.crowd.is_empty());
+        assert!(level.targets.is_empty());
+        assert!(level.crowd.iter().all(|c| c.target.is_none()));
+    }
+}
@@ -0,0 +1,493 @@
+//! Level drawing.
+//!
+//! Draws the level state. Nothing in here updates the level.
+
+use super::{utils::text::wrap_text, *};
+
+const BAR_BG_WIDTH: f32 = GROUND_WIDTH;
+const BAR_WIDTH: f32 = GROUND_WIDTH - 8.0;
+const BAR_HEIGHT: f32 = 20.0;
+const BAR_OFFSET: f32 = 4.0;
+
+impl Level {
+    /// Draws the level.
+    pub fn draw(&self, assets: &AssetBundle, score: [f32; 2]) {
+        // Draw the ground
+        self.draw_ground(assets);
+
+        // Draw level number
+        let text_size = measure_text("TEXT", Some(assets.font), 32, 1.0);
+        draw_text_ex(
+            &format!("{:.0}", score[0]),
+            110.0,
+            70.0,
+            TextParams {
+                font: assets.font,
+                font_size: 32,
+                color: WHITE,
+                ..Default::default()
+            },
+        );
+        draw_texture_ex(
+            assets.skull,
+            60.0,
+            70.0 - text_size.height * 1.25,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(text_size.height * 1.5, text_size.height * 1.5)),
+                ..Default::default()
+            },
+        );
+
+        // Draw the total score
+        draw_text_ex(
+            &format!("{:.0}", score[1]),
+            65.0,
+            110.0,
+            TextParams {
+                font: assets.font,
+                font_size: 32,
+                color: WHITE,
+                ..Default::default()
+            },
+        );
+
+        self.draw_crowd(assets);
+        self.draw_selection();
+        self.draw_hints(assets);
+
+        self.draw_progress_bar(assets);
+    }
+
+    /// Draws the ground.
+    fn draw_ground(&self, assets: &AssetBundle) {
+        draw_texture_ex(
+            assets.ground,
+            GAME_WIDTH - GROUND_WIDTH - 50.0,
+            GAME_HEIGHT - GROUND_HEIGHT - 30.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(GROUND_WIDTH, GROUND_HEIGHT)),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Draws the crowd.
+    fn draw_crowd(&self, assets: &AssetBundle) {
+        // Draw from top to bottom so the characters in front overlap the ones behind
+        let mut order: Vec<&Character> = self.crowd.iter().collect();
+        order.sort_by(|a, b| a.y.total_cmp(&b.y));
+        for character in order {
+            character.draw(assets);
+        }
+    }
+
+    /// Draws the hints for the target characters, a dossier for each target stacked in
+    /// the hints panel. The dossiers of dead targets fold up to their title.
+    fn draw_hints(&self, assets: &AssetBundle) {
+        let (x, y) = (70.0, GAME_HEIGHT - GROUND_HEIGHT + 110.0);
+
+        // Draw hints background
+        draw_texture_ex(
+            assets.frame_long,
+            x - 20.0,
+            GAME_HEIGHT - GROUND_HEIGHT - 30.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(280.0, GROUND_HEIGHT)),
+                ..Default::default()
+            },
+        );
+
+        // Draw objective text
+        let text_size = measure_text("TEXT", Some(assets.font), 32, 1.0);
+        let whom = match self.targets.len() {
+            1 => "this person:".to_string(),
+            n => format!("these {} people:", n),
+        };
+        for (i, line) in ["Your mission", "is to find", &whom]
+            .into_iter()
+            .enumerate()
+        {
+            draw_text_ex(
+                line,
+                x,
+                y - 80.0 + (text_size.height + 10.0) * i as f32,
+                TextParams {
+                    font: assets.font,
+                    font_size: 32,
+                    color: WHITE,
+                    ..Default::default()
+                },
+            );
+        }
+
+        if assets.word_hints() {
+            self.draw_word_hints(assets, x, y);
+            return;
+        }
+
+        let padding = 10.0;
+        // Shrink the hints to fit the panel if there are more than 3
+        let titles = self.dossier_title_height() * self.targets.len() as f32;
+        let rows: usize = (0..self.targets.len())
+            .filter(|&target| self.target_alive(target))
+            .map(|target| self.targets[target].clues.len())
+            .sum();
+        let row = (GAME_HEIGHT - 40.0 - y - titles) / rows.max(1) as f32;
+        let gap = (row / 5.0).min(20.0);
+        let size = (row - padding - gap).min(108.0);
+
+        let mut row_y = y;
+        for (index, target) in self.targets.iter().enumerate() {
+            row_y += self.draw_dossier_title(assets, index, x, row_y);
+            if !self.target_alive(index) {
+                continue;
+            }
+
+            let tint = assets.traits.tint(&target.traits, assets.palette());
+            let color_variant = assets.traits.color_variant(&target.traits);
+            let target_tint = color_variant.map(|variant| (tint, variant));
+            for clue in &target.clues {
+                draw_clue(assets, clue, x, row_y, size, padding, target_tint);
+                row_y += size + padding + gap;
+            }
+        }
+    }
+
+    /// Returns the height of a dossier's title in the hints, none if there's one target.
+    fn dossier_title_height(&self) -> f32 {
+        if self.targets.len() > 1 {
+            30.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Draws the title of the dossier of the target with the given index at (`x`, `y`),
+    /// if there's more than one target. Returns its height.
+    fn draw_dossier_title(&self, assets: &AssetBundle, target: usize, x: f32, y: f32) -> f32 {
+        let height = self.dossier_title_height();
+        if height > 0.0 {
+            let (title, color) = match self.target_alive(target) {
+                true => (format!("Target {}", target + 1), WHITE),
+                false => (
+                    format!("Target {} - done", target + 1),
+                    assets.palette().theme().matched,
+                ),
+            };
+            draw_text_ex(
+                &title,
+                x,
+                y + 20.0,
+                TextParams {
+                    font: assets.font,
+                    font_size: 24,
+                    color,
+                    ..Default::default()
+                },
+            );
+        }
+        height
+    }
+
+    /// Draws the clues about each target as a sentence from (`x`, `y`), smaller if they
+    /// don't fit the panel.
+    fn draw_word_hints(&self, assets: &AssetBundle, x: f32, y: f32) {
+        let sentences: Vec<Option<String>> = (self.targets.iter().enumerate())
+            .map(|(index, target)| {
+                let alive = self.target_alive(index);
+                alive.then(|| clues::describe(&target.clues, assets))
+            })
+            .collect();
+        let titles = self.dossier_title_height() * self.targets.len() as f32;
+        let (width, height) = (240.0, GAME_HEIGHT - 40.0 - y - titles);
+        let line_height =
+            |font_size: u16| measure_text("TEXT", Some(assets.font), font_size, 1.0).height * 1.5;
+        let wrap = |font_size: u16| -> Vec<Vec<String>> {
+            (sentences.iter().flatten())
+                .map(|sentence| wrap_text(sentence, assets.font, font_size, width))
+                .collect()
+        };
+        let (font_size, mut paragraphs) = [32, 24, 16]
+            .into_iter()
+            .map(|font_size| (font_size, wrap(font_size)))
+            .find(|(font_size, paragraphs)| {
+                let lines: usize = paragraphs.iter().map(Vec::len).sum();
+                lines as f32 * line_height(*font_size) <= height
+            })
+            .unwrap_or_else(|| (16, wrap(16)));
+        paragraphs.reverse(); // Popped in target order
+
+        let mut line_y = y;
+        for (index, sentence) in sentences.iter().enumerate() {
+            line_y += self.draw_dossier_title(assets, index, x, line_y);
+            if sentence.is_none() {
+                continue;
+            }
+            for line in paragraphs.pop().unwrap_or_default() {
+                line_y += line_height(font_size);
+                draw_text_ex(
+                    &line,
+                    x,
+                    line_y,
+                    TextParams {
+                        font: assets.font,
+                        font_size,
+                        color: WHITE,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    /// Draws the level progress bar.
+    fn draw_progress_bar(&self, assets: &AssetBundle) {
+        let [plenty, mid, low] = assets.palette().theme().timer;
+        let bar_color = if self.timer < (self.time_limit / 3.0) {
+            low
+        } else if self.timer < (self.time_limit / 3.0 * 2.0) {
+            mid
+        } else {
+            plenty
+        };
+
+        // Draw progress bar background
+        let bar_x = GAME_WIDTH - GROUND_WIDTH - 50.0;
+        let bar_y = 80.0;
+        draw_texture_ex(
+            assets.bar[1],
+            bar_x,
+            bar_y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(BAR_BG_WIDTH, BAR_HEIGHT)),
+                ..Default::default()
+            },
+        );
+
+        // Draw progress bar
+        let progress = (self.timer / self.time_limit).clamp(0.0, 1.0);
+        draw_texture_ex(
+            assets.bar[0],
+            bar_x + BAR_OFFSET,
+            bar_y,
+            bar_color,
+            DrawTextureParams {
+                dest_size: Some(vec2(BAR_WIDTH * progress, BAR_HEIGHT)),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Draws an outline around the character selected with the keyboard.
+    fn draw_selection(&self) {
+        if let Some(character) = self.selected.map(|i| &self.crowd[i]) {
+            if character.spawned && !character.dead {
+                draw_rectangle_lines(
+                    character.x,
+                    character.y + self.tuning.click_offset,
+                    CHAR_WIDTH,
+                    CHAR_HEIGHT - self.tuning.click_offset,
+                    4.0,
+                    WHITE,
+                );
+            }
+        }
+    }
+
+    /// Draws the target outlines and, over each character, which of the clues about the
+    /// closest target they fit.
+    pub fn draw_reveal(&self, assets: &AssetBundle) {
+        let size = 20.0;
+
+        let theme = assets.palette().theme();
+        self.draw_target_outline(theme.target);
+
+        let alive = self
+            .crowd
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.spawned && !c.dead);
+        for (index, character) in alive {
+            // Against the target they're the closest to
+            let clues = &self.targets[self.closest_target(index)].clues;
+            let width = clues.len() as f32 * (size + 2.0);
+            let x = character.x + (CHAR_WIDTH - width) / 2.0;
+
+            for (i, clue) in clues.iter().enumerate() {
+                let matched = clue.fits(&self.crowd, index, self.bounds);
+                let color = if matched { theme.matched } else { theme.missed };
+                let square_x = x + i as f32 * (size + 2.0);
+                // Hollow misses with the markers, so they aren't told apart by color alone
+                let hollow = !matched && assets.markers();
+                if hollow {
+                    draw_rectangle_lines(square_x, character.y, size, size, 4.0, color);
+                } else {
+                    draw_rectangle(square_x, character.y, size, size, color);
+                }
+                // The initial of the clue's category, P for position
+                let name = clue.stat_name(&assets.traits);
+                let initial = name.chars().next().map(String::from).unwrap_or_default();
+                draw_text_ex(
+                    &initial,
+                    square_x + 5.0,
+                    character.y + size - 4.0,
+                    TextParams {
+                        font: assets.font,
+                        font_size: 16,
+                        color: if hollow { color } else { BG_PURPLE },
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+
+    /// Draws an outline around the target characters in the given color.
+    fn draw_target_outline(&self, color: Color) {
+        for character in self.crowd.iter() {
+            if character.target.is_some() {
+                draw_rectangle_lines(
+                    character.x,
+                    character.y + self.tuning.click_offset,
+                    CHAR_WIDTH,
+                    CHAR_HEIGHT - self.tuning.click_offset,
+                    5.0,
+                    color,
+                );
+            }
+        }
+    }
+}
+
+/// Draws a clue's hint at (`x`, `y`): a framed `size` square icon with `padding`
+/// around it and the clue's label beside it. Trait variants about the target are drawn
+/// in the target's tint.
+fn draw_clue(
+    assets: &AssetBundle,
+    clue: &Clue,
+    x: f32,
+    y: f32,
+    size: f32,
+    padding: f32,
+    target_tint: Option<(Color, usize)>,
+) {
+    // Draw frame
+    draw_texture_ex(
+        assets.frame,
+        x,
+        y,
+        WHITE,
+        DrawTextureParams {
+            dest_size: Some(vec2(size + padding, size + padding)),
+            ..Default::default()
+        },
+    );
+
+    // Draw hint icon, the variant of the clue's trait or the half of the level
+    let (hint_x, hint_y) = (x + padding / 2.0, y + padding / 2.0);
+    match *clue {
+        Clue::Has(part, variant) | Clue::Not(part, variant) | Clue::Extreme(_, part, variant) => {
+            draw_variant(assets, part, variant, hint_x, hint_y, size, target_tint);
+        }
+        // Someone else, whose tint isn't known
+        Clue::NextTo(part, variant) => {
+            draw_variant(assets, part, variant, hint_x, hint_y, size, None);
+        }
+        Clue::Half(direction) => {
+            let half = size / 2.0;
+            let (half_x, half_y, width, height) = match direction {
+                Direction::Up => (hint_x, hint_y, size, half),
+                Direction::Down => (hint_x, hint_y + half, size, half),
+                Direction::Left => (hint_x, hint_y, half, size),
+                Direction::Right => (hint_x + half, hint_y, half, size),
+            };
+            draw_rectangle_lines(hint_x, hint_y, size, size, 4.0, WHITE);
+            draw_rectangle(half_x, half_y, width, height, WHITE);
+        }
+    }
+    if let Clue::Not(..) = clue {
+        let missed = assets.palette().theme().missed;
+        let (right, bottom) = (hint_x + size, hint_y + size);
+        draw_line(hint_x, hint_y, right, bottom, 6.0, missed);
+        draw_line(right, hint_y, hint_x, bottom, 6.0, missed);
+    }
+
+    // Draw hint description text, smaller if it doesn't fit
+    let label = clue.label(&assets.traits);
+    let text_x = x + size + padding + 20.0;
+    let font_size = [32, 24, 16]
+        .into_iter()
+        .find(|&font_size| {
+            measure_text(&label, Some(assets.font), font_size, 1.0).width <= x + 240.0 - text_x
+        })
+        .unwrap_or(16);
+    let text_size = measure_text("TEXT", Some(assets.font), 32, 1.0);
+    draw_text_ex(
+        &label,
+        text_x,
+        y + (size + text_size.height) / 2.0,
+        TextParams {
+            font: assets.font,
+            font_size,
+            color: WHITE,
+            ..Default::default()
+        },
+    );
+}
+
+/// Draws the given variant of a trait category in a `size` square, for the hints.
+///
+/// Tinted sprites are drawn in the given tint and its color variant's marker, or in
+/// white if there's none. Colors are drawn as swatches.
+fn draw_variant(
+    assets: &AssetBundle,
+    part: usize,
+    variant: usize,
+    x: f32,
+    y: f32,
+    size: f32,
+    tint: Option<(Color, usize)>,
+) {
+    let category = assets.traits.get(part);
+    if category.is_color() {
+        let name = category.variant_name(variant);
+        let color = assets.palette().tint(&name, category.color(variant));
+        if assets.markers() {
+            draw_marker(variant, x, y, size, color);
+        } else {
+            draw_rectangle(x, y, size, size, color);
+        }
+        return;
+    }
+
+    let color = match tint {
+        Some((color, _)) if category.tint => color,
+        _ => WHITE,
+    };
+    // The empty texture if it's the variant for none of the trait
+    if category.none == Some(variant) {
+        draw_texture_ex(
+            assets.empty,
+            x,
+            y,
+            color,
+            DrawTextureParams {
+                dest_size: Some(vec2(size, size)),
+                ..Default::default()
+            },
+        );
+    } else {
+        assets.draw_char_part(part, variant, x, y, vec2(size, size), color);
+    }
+
+    // The tint's pattern in the corner
+    if let Some((color, color_variant)) = tint.filter(|_| category.tint && assets.markers()) {
+        let marker_size = (size / 4.0).max(12.0);
+        let (marker_x, marker_y) = (x + size - marker_size, y + size - marker_size);
+        draw_marker(color_variant, marker_x, marker_y, marker_size, color);
+    }
+}
@@ -3,118 +3,121 @@
 //! Game level logic and implementation.
 
 mod character;
+mod clues;
+mod draw;
+mod movement;
 use super::*;
 use character::Character;
-use macroquad::rand::{gen_range, ChooseRandom};
-
-const BAR_BG_WIDTH: f32 = GROUND_WIDTH;
-const BAR_WIDTH: f32 = GROUND_WIDTH - 8.0;
-const BAR_HEIGHT: f32 = 20.0;
-const BAR_OFFSET: f32 = 4.0;
+pub use clues::Clue;
+use movement::Movement;
+
+/// One of the targets of the level's contract.
+pub struct Target {
+    /// The target's traits, the variant of each trait category.
+    pub traits: Vec<usize>,
+    /// The clues about the target, which no other character fits all of.
+    pub clues: Vec<Clue>,
+}
 
 pub struct Level {
     /// The crowd of characters in the level.
     pub crowd: Vec<Character>,
-    /// The target character's traits.
-    pub target_traits: [usize; CHAR_PARTS_COUNT],
-    /// The indices of the target character's unique traits.
-    pub unique_traits_indices: Vec<usize>,
-    /// The assets bundle.
-    pub assets: Rc<AssetBundle>,
-    /// The color to draw the hints in.
-    pub hints_color: Color,
+    /// The targets of the contract, all of which must be killed to clear the level.
+    pub targets: Vec<Target>,
     /// The timer of the level.
     pub timer: f32,
+    /// The time the level started with.
+    pub time_limit: f32,
     /// Whether the timer should be running.
     pub timer_on: bool,
-    /// The delay between spawning characters.
+    /// The time left until the next character spawns.
     pub spawn_timer: f32,
+    /// The delay between spawning characters.
+    pub spawn_delay: f32,
+    /// The random number generator used to generate the crowd.
+    pub rng: Rng,
+    /// The crowd index of the character selected with the keyboard, if any.
+    pub selected: Option<usize>,
+    /// The area the characters spawn and wander in.
+    pub bounds: Rect,
+    /// How fast the characters walk, in pixels per second.
+    pub walk_speed: f32,
+    /// Gameplay tuning values.
+    pub tuning: Rc<Tuning>,
+    /// The number of variants of each trait category.
+    pub part_counts: Vec<usize>,
+    /// Whether "next to" clues can be picked. They can't when the run goes on after a
+    /// wrong kill, which could kill the neighbour a clue is about.
+    pub next_to_clues: bool,
     /// Iterator of the crowd used for spawning characters with the delay.
     crowd_iter: usize,
 }
 
 impl Level {
-    /// Initializes a level struct.
-    pub fn init(assets: &Rc<AssetBundle>) -> Level {
+    /// Initializes a level struct with its random number generator seeded with `seed`,
+    /// generating characters out of `part_counts` variants of each trait category.
+    pub fn init(seed: u64, tuning: &Rc<Tuning>, part_counts: Vec<usize>) -> Level {
+        let time_limit = tuning.level_time.at(0.0);
         Level {
             crowd: Vec::new(),
-            unique_traits_indices: Vec::new(),
-            target_traits: [0; CHAR_PARTS_COUNT],
-            assets: Rc::clone(assets),
-            hints_color: rand_color(),
-            timer: LEVEL_TIME,
+            targets: Vec::new(),
+            timer: time_limit,
+            time_limit,
             timer_on: false,
-            spawn_timer: SPAWN_DELAY,
+            spawn_timer: tuning.spawn_delay,
+            spawn_delay: tuning.spawn_delay,
             crowd_iter: 0,
+            rng: Rng::new(seed),
+            selected: None,
+            bounds: Rect::default(),
+            walk_speed: 0.0,
+            tuning: Rc::clone(tuning),
+            part_counts,
+            next_to_clues: true,
         }
     }
 
-    /// Draws the level.
-    ///
-    /// Returns `true` if the timer is up. Returns `false` otherwise.
-    pub fn draw(&mut self, score: [f32; 2]) -> bool {
-        // Draw the ground
-        self.draw_ground();
-
-        // Draw level number
-        let text_size = measure_text("TEXT", Some(self.assets.font), 32, 1.0);
-        draw_text_ex(
-            &format!("{:.0}", score[0]),
-            110.0,
-            70.0,
-            TextParams {
-                font: self.assets.font,
-                font_size: 32,
-                color: WHITE,
-                ..Default::default()
-            },
-        );
-        draw_texture_ex(
-            self.assets.skull,
-            60.0,
-            70.0 - text_size.height * 1.25,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(vec2(text_size.height * 1.5, text_size.height * 1.5)),
-                ..Default::default()
-            },
-        );
-
-        // Draw the total score
-        draw_text_ex(
-            &format!("{:.0}", score[1]),
-            65.0,
-            110.0,
-            TextParams {
-                font: self.assets.font,
-                font_size: 32,
-                color: WHITE,
-                ..Default::default()
-            },
-        );
-
-        self.draw_crowd();
-        self.draw_hints();
-        // self.draw_target_outline();
-
-        // Update and draw the timer
-        self.draw_progress_bar()
+    /// Generates a crowd of `num` random characters at positions within `bounds`, with
+    /// `targets` targets and `hints` clues about each one that no other character fits
+    /// all of. The first characters in the crowd are the targets, leaving at least one
+    /// character that isn't unless the crowd is a single character. There can be fewer
+    /// targets if they can't all be told apart, see `pick_clues`.
+    pub fn gen_crowd(&mut self, num: usize, targets: usize, hints: usize, bounds: Rect) {
+        self.scatter_crowd(num, bounds);
+
+        // Make sure no other character fits all of a target's clues
+        let targets = targets.min(self.crowd.len().saturating_sub(1)).max(1);
+        for i in 0..targets {
+            self.crowd[i].target = Some(i);
+        }
+        let clues = self.pick_clues(targets, hints);
+        for character in &mut self.crowd[clues.len()..] {
+            character.target = None; // Dropped from the contract
+        }
+        self.targets = clues
+            .into_iter()
+            .enumerate()
+            .map(|(i, clues)| Target {
+                traits: self.crowd[i].traits.clone(),
+                clues,
+            })
+            .collect();
     }
 
-    /// Generates a crowd of `num` random characters between the given coordinates.
-    /// The first character in the crowd is the target.
-    pub fn gen_crowd(&mut self, num: usize, x_min: f32, x_max: f32, y_min: f32, y_max: f32) {
-        let mut traits_range: Vec<usize> = (0..CHAR_PARTS_COUNT).collect();
-        traits_range.shuffle(); // Shuffle the traits range
-        self.unique_traits_indices = traits_range[0..3].to_vec(); // Pick the first 3 traits as the unique traits
-        self.unique_traits_indices.sort(); // Sort the unique traits indices
-
+    /// Generates a crowd of up to `num` random characters at positions within `bounds`,
+    /// without targets. Fewer fit if the characters can't be placed without overlapping.
+    pub fn scatter_crowd(&mut self, num: usize, bounds: Rect) {
+        let (x_min, x_max) = (bounds.left(), bounds.right());
+        let (y_min, y_max) = (bounds.top(), bounds.bottom());
+        self.bounds = bounds;
         self.crowd = Vec::new(); // Clear the crowd
         self.crowd_iter = 0; // Reset the crowd iterator
+        self.selected = None;
         self.timer_on = false;
+        self.targets = Vec::new();
 
         // Generate `num` characters scattered around the level.
-        for i in 0..num {
+        for _ in 0..num {
             // Generate a position for the character
             let mut pos_valid = false;
             let (mut attempt, attempt_max) = (0, 1000);
@@ -123,8 +126,8 @@ impl Level {
 
             while !pos_valid && attempt < attempt_max {
                 pos_valid = true;
-                x = gen_range(x_min, x_max);
-                y = gen_range(y_min, y_max);
+                x = self.rng.gen_range(x_min, x_max);
+                y = self.rng.gen_range(y_min, y_max);
 
                 // Check if the position is valid (not colliding with another character)
                 for character in &self.crowd {
@@ -144,284 +147,146 @@ impl Level {
                 break; // Stop generating characters if the max attempts is reached
             }
 
-            // Generate a random character
-            loop {
-                let char_rand: [usize; CHAR_PARTS_COUNT] = [
-                    gen_range(0, ARMS_COUNT),
-                    gen_range(0, BODY_COUNT),
-                    gen_range(0, FACE_COUNT),
-                    gen_range(0, HAT_COUNT),
-                    gen_range(0, LEGS_COUNT),
-                ];
-
-                // Make sure no other character has the exact same traits as the target unique traits
-                if i > 0
-                    && (self.target_traits[self.unique_traits_indices[0]]
-                        == char_rand[self.unique_traits_indices[0]]
-                        && self.target_traits[self.unique_traits_indices[1]]
-                            == char_rand[self.unique_traits_indices[1]]
-                        && self.target_traits[self.unique_traits_indices[2]]
-                            == char_rand[self.unique_traits_indices[2]])
-                {
-                    // generate a new character
-                    continue;
-                }
-
-                // Add the character to the crowd
-                self.crowd.push(Character::init(
-                    x,
-                    y,
-                    [
-                        self.assets.char_arms[char_rand[0]],
-                        self.assets.char_body[char_rand[1]],
-                        self.assets.char_face[char_rand[2]],
-                        self.assets.char_hat[char_rand[3]],
-                        self.assets.char_legs[char_rand[4]],
-                        self.assets.blood, // blood texture (for when the character is killed)
-                    ],
-                ));
-
-                // Set the target traits
-                if i == 0 {
-                    self.target_traits = char_rand;
-                }
-
-                break;
-            }
+            // Add a random character to the crowd, idling for a random time before it starts walking
+            let mut character = Character::init(x, y, self.rand_traits());
+            let idle = self.rng.gen_range(0.0, self.tuning.wander.idle_max);
+            character.movement = Movement::Idle(idle);
+            self.crowd.push(character);
         }
-
-        self.crowd[0].is_target = true;
     }
 
-    /// Draws the ground.
-    fn draw_ground(&self) {
-        draw_texture_ex(
-            self.assets.ground,
-            GAME_WIDTH - GROUND_WIDTH - 50.0,
-            GAME_HEIGHT - GROUND_HEIGHT - 30.0,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(vec2(GROUND_WIDTH, GROUND_HEIGHT)),
-                ..Default::default()
-            },
-        );
+    /// Returns random traits, a variant of each trait category.
+    fn rand_traits(&mut self) -> Vec<usize> {
+        (0..self.part_counts.len())
+            .map(|category| self.rng.gen_range(0, self.part_counts[category]))
+            .collect()
     }
 
-    /// Draws the crowd.
-    fn draw_crowd(&mut self) {
-        self.spawn_timer -= get_frame_time();
+    /// Advances the spawn timer by `dt` seconds and spawns the next character when it's due.
+    ///
+    /// Returns the crowd index of the spawned character, if any.
+    pub fn update_spawn(&mut self, dt: f32) -> Option<usize> {
+        self.spawn_timer -= dt;
 
-        // Spawn a new character every `SPAWN_DELAY` seconds
+        // Spawn a new character every `spawn_delay` seconds
         if self.spawn_timer <= 0.0 && self.crowd_iter < self.crowd.len() {
-            self.crowd[self.crowd_iter].spawned = true;
-            self.spawn_timer = SPAWN_DELAY;
+            let index = self.crowd_iter;
+            self.crowd[index].spawned = true;
+            self.spawn_timer = self.spawn_delay;
             self.crowd_iter += 1;
 
-            play_sound_once(self.assets.spawn_sound);
-
             // Start the timer when the last character is spawned
             if self.crowd_iter >= self.crowd.len() {
                 self.timer_on = true;
             }
+            return Some(index);
         }
-
-        // Draw the crowd
-        for character in self.crowd.iter_mut() {
-            character.draw(true);
-        }
+        None
     }
 
-    /// Draws the hints for the target character.
-    fn draw_hints(&self) {
-        let hints_text = ["Arms", "Body", "Face", "Hat", "Legs"];
-        let (x, y) = (70.0, GAME_HEIGHT - GROUND_HEIGHT + 110.0);
-        let size = 108.0;
-        let padding = 10.0;
-        let gap = 20.0;
-        let mut hints_color;
-
-        // Draw hints background
-        draw_texture_ex(
-            self.assets.frame_long,
-            x - 20.0,
-            GAME_HEIGHT - GROUND_HEIGHT - 30.0,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(vec2(280.0, GROUND_HEIGHT)),
-                ..Default::default()
-            },
-        );
-
-        // Draw objective text
-        let text_size = measure_text("TEXT", Some(self.assets.font), 32, 1.0);
-        draw_text_ex(
-            "Your mission",
-            x,
-            y - 80.0,
-            TextParams {
-                font: self.assets.font,
-                font_size: 32,
-                color: WHITE,
-                ..Default::default()
-            },
-        );
-        draw_text_ex(
-            "is to find",
-            x,
-            y - 80.0 + text_size.height + 10.0,
-            TextParams {
-                font: self.assets.font,
-                font_size: 32,
-                color: WHITE,
-                ..Default::default()
-            },
-        );
-        draw_text_ex(
-            "who has...",
-            x,
-            y - 80.0 + (text_size.height + 10.0) * 2.0,
-            TextParams {
-                font: self.assets.font,
-                font_size: 32,
-                color: WHITE,
-                ..Default::default()
-            },
-        );
-
-        // Draw hints
-        for i in 0..3 {
-            let mut texture = match self.unique_traits_indices[i] {
-                0 => self.assets.char_arms[self.target_traits[0]],
-                1 => self.assets.char_body[self.target_traits[1]],
-                2 => self.assets.char_face[self.target_traits[2]],
-                3 => self.assets.char_hat[self.target_traits[3]],
-                4 => self.assets.char_legs[self.target_traits[4]],
-                _ => panic!("Should never happen. You broke the game!"),
-            };
-
-            // If the character has no hat, draw the empty texture instead.
-            if texture == self.assets.char_hat[0] {
-                texture = self.assets.empty;
-            }
-
-            // Don't colorize the face and hat.
-            if self.unique_traits_indices[i] == 2 || self.unique_traits_indices[i] == 3 {
-                hints_color = WHITE;
-            } else {
-                hints_color = self.hints_color;
-            }
-
-            // Draw frame
-            draw_texture_ex(
-                self.assets.frame,
-                x,
-                y + i as f32 * (size + padding + gap),
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(size + padding, size + padding)),
-                    ..Default::default()
-                },
-            );
-
-            // Draw hint
-            draw_texture_ex(
-                texture,
-                x + padding / 2.0,
-                y + i as f32 * (size + padding + gap) + padding / 2.0,
-                hints_color,
-                DrawTextureParams {
-                    dest_size: Some(vec2(size, size)),
-                    ..Default::default()
-                },
-            );
-
-            // Draw hint description text
-            draw_text_ex(
-                hints_text[self.unique_traits_indices[i]],
-                x + size + padding + 20.0,
-                y + i as f32 * (size + padding + gap) + (size + text_size.height) / 2.0,
-                TextParams {
-                    font: self.assets.font,
-                    font_size: 32,
-                    color: WHITE,
-                    ..Default::default()
-                },
-            );
-        }
-    }
-
-    /// Updates and draws the level progress bar.
+    /// Advances the level timer by `dt` seconds.
     ///
     /// Returns `true` if the timer is up. Returns `false` otherwise.
-    fn draw_progress_bar(&mut self) -> bool {
-        let bar_color = if self.timer < (LEVEL_TIME / 3.0) {
-            COLOR_RED
-        } else if self.timer < (LEVEL_TIME / 3.0 * 2.0) {
-            COLOR_YELLOW
-        } else {
-            COLOR_GREEN
-        };
-
-        // Draw progress bar background
-        let bar_x = GAME_WIDTH - GROUND_WIDTH - 50.0;
-        let bar_y = 80.0;
-        draw_texture_ex(
-            self.assets.bar[1],
-            bar_x,
-            bar_y,
-            WHITE,
-            DrawTextureParams {
-                dest_size: Some(vec2(BAR_BG_WIDTH, BAR_HEIGHT)),
-                ..Default::default()
-            },
-        );
-
-        // Draw progress bar
-        let progress = self.timer / LEVEL_TIME;
-        draw_texture_ex(
-            self.assets.bar[0],
-            bar_x + BAR_OFFSET,
-            bar_y,
-            bar_color,
-            DrawTextureParams {
-                dest_size: Some(vec2(BAR_WIDTH * progress, BAR_HEIGHT)),
-                ..Default::default()
-            },
-        );
-
-        // Update timer and check if it's up
+    pub fn update_timer(&mut self, dt: f32) -> bool {
         if !self.timer_on {
             return false;
         } else if self.timer > 0.0 {
-            self.timer -= get_frame_time();
+            self.timer -= dt;
         } else {
             return true;
         }
         false
     }
 
-    #[allow(unused)]
-    /// Draws an outline around the target character.
-    fn draw_target_outline(&self) {
-        for character in self.crowd.iter() {
-            if character.is_target {
-                draw_rectangle_lines(
-                    character.x,
-                    character.y + CLICK_OFFSET,
-                    CHAR_WIDTH,
-                    CHAR_HEIGHT - CLICK_OFFSET,
-                    5.0,
-                    COLOR_RED,
+    /// Returns the crowd index of the spawned, alive character at the given position, if any.
+    ///
+    /// Picks the frontmost (lowest on the screen) character if several overlap there.
+    pub fn character_at(&self, x: f32, y: f32) -> Option<usize> {
+        self.crowd
+            .iter()
+            .enumerate()
+            .filter(|(_, character)| {
+                character.spawned
+                    && !character.dead
+                    && x >= character.x
+                    && x <= character.x + CHAR_WIDTH
+                    && y >= character.y + self.tuning.click_offset
+                    && y <= character.y + CHAR_HEIGHT
+            })
+            .max_by(|(_, a), (_, b)| a.y.total_cmp(&b.y))
+            .map(|(i, _)| i)
+    }
+
+    /// Moves the keyboard selection to the nearest spawned character in the given direction.
+    ///
+    /// Selects the character closest to the middle of the ground if nothing is selected yet.
+    pub fn select(&mut self, direction: Direction) {
+        let alive = |character: &&Character| character.spawned && !character.dead;
+
+        let from = match self.selected {
+            Some(index) => self.crowd[index].center(self.tuning.click_offset),
+            None => {
+                let middle = (
+                    GAME_WIDTH - GROUND_WIDTH / 2.0 - 50.0,
+                    GAME_HEIGHT - GROUND_HEIGHT / 2.0 - 30.0,
                 );
+                self.selected = self
+                    .crowd
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, character)| alive(character))
+                    .map(|(i, character)| {
+                        let (x, y) = character.center(self.tuning.click_offset);
+                        (i, (x - middle.0).powi(2) + (y - middle.1).powi(2))
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(i, _)| i);
+                return;
+            }
+        };
+
+        // Distance along the direction, and a penalty for how far off to the side it is
+        let mut best: Option<(usize, f32)> = None;
+        for (i, character) in self.crowd.iter().enumerate().filter(|(_, c)| alive(c)) {
+            let (x, y) = character.center(self.tuning.click_offset);
+            let (along, side) = match direction {
+                Direction::Up => (from.1 - y, x - from.0),
+                Direction::Down => (y - from.1, x - from.0),
+                Direction::Left => (from.0 - x, y - from.1),
+                Direction::Right => (x - from.0, y - from.1),
+            };
+            if along <= 0.0 {
+                continue; // Not in the pressed direction
+            }
+            let distance = along + side.abs() * 2.0;
+            if best.is_none_or(|(_, d)| distance < d) {
+                best = Some((i, distance));
             }
         }
+
+        if let Some((i, _)) = best {
+            self.selected = Some(i);
+        }
+    }
+
+    /// Returns `true` if the target with the given index is still alive.
+    pub fn target_alive(&self, target: usize) -> bool {
+        self.crowd
+            .iter()
+            .any(|character| character.target == Some(target) && !character.dead)
+    }
+
+    /// Returns the number of targets still alive.
+    pub fn targets_left(&self) -> usize {
+        (0..self.targets.len())
+            .filter(|&target| self.target_alive(target))
+            .count()
     }
 
-    /// Finds the target character in the crowd and returns a mutable reference to it.
-    pub fn get_target(&mut self) -> &mut Character {
+    /// Returns mutable references to the target characters in the crowd.
+    pub fn target_characters(&mut self) -> impl Iterator<Item = &mut Character> {
         self.crowd
             .iter_mut()
-            .find(|character| character.is_target)
-            .unwrap()
+            .filter(|character| character.target.is_some())
     }
 }
@@ -0,0 +1,90 @@
+//! Crowd movement.
+//!
+//! Characters wander around the ground: they idle for a while, walk to a random
+//! destination inside the level bounds and push each other apart so they don't overlap.
+//! The crowd stands still in levels with clues about where characters stand.
+
+use super::*;
+
+/// How strongly overlapping characters push each other apart, per second.
+const SEPARATION_STRENGTH: f32 = 3.0;
+
+/// What a character is doing while wandering around.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Movement {
+    /// Standing still for the given number of seconds.
+    Idle(f32),
+    /// Walking to the given position.
+    Walking(Vec2),
+}
+
+impl Level {
+    /// Moves the spawned, alive characters around the level by `dt` seconds.
+    pub fn update_movement(&mut self, dt: f32) {
+        if dt <= 0.0 || !self.wanders() {
+            return;
+        }
+        let (idle_min, idle_max) = (self.tuning.wander.idle_min, self.tuning.wander.idle_max);
+        let separation = self.tuning.wander.separation;
+
+        for i in 0..self.crowd.len() {
+            if !self.crowd[i].spawned || self.crowd[i].dead {
+                continue;
+            }
+            let pos = vec2(self.crowd[i].x, self.crowd[i].y);
+
+            // Walk to the destination, or pick a new one once done idling
+            let walk = match self.crowd[i].movement {
+                Movement::Idle(time) if time > dt => {
+                    self.crowd[i].movement = Movement::Idle(time - dt);
+                    Vec2::ZERO
+                }
+                Movement::Idle(_) => {
+                    let destination = vec2(
+                        self.rng.gen_range(self.bounds.left(), self.bounds.right()),
+                        self.rng.gen_range(self.bounds.top(), self.bounds.bottom()),
+                    );
+                    self.crowd[i].movement = Movement::Walking(destination);
+                    Vec2::ZERO
+                }
+                Movement::Walking(destination) => {
+                    let to_destination = destination - pos;
+                    if to_destination.length() <= self.walk_speed * dt {
+                        let idle = self.rng.gen_range(idle_min, idle_max);
+                        self.crowd[i].movement = Movement::Idle(idle);
+                        to_destination / dt // Arrive exactly at the destination
+                    } else {
+                        to_destination.normalize_or_zero() * self.walk_speed
+                    }
+                }
+            };
+
+            // Push away from the characters that are too close
+            let mut push = Vec2::ZERO;
+            for (j, other) in self.crowd.iter().enumerate() {
+                if j == i || !other.spawned || other.dead {
+                    continue;
+                }
+                let away = pos - vec2(other.x, other.y);
+                let distance = away.length();
+                if distance < separation {
+                    let direction = if distance > 0.0 {
+                        away / distance
+                    } else {
+                        vec2(1.0, 0.0)
+                    };
+                    push += direction * (separation - distance);
+                }
+            }
+
+            let character = &mut self.crowd[i];
+            character.velocity = walk + push * SEPARATION_STRENGTH;
+            let pos = (pos + character.velocity * dt).clamp(
+                vec2(self.bounds.left(), self.bounds.top()),
+                vec2(self.bounds.right(), self.bounds.bottom()),
+            );
+            character.x = pos.x;
+            character.y = pos.y;
+        }
+    }
+}
@@ -2,17 +2,40 @@
 //!
 //! Game logic and implementation.
 
+mod achievements;
 mod asset_bundle;
+mod game_core;
 mod game_states;
+mod highscores;
+mod input;
+mod leaderboard;
 mod level;
+mod palette;
 mod renderer;
+mod replay;
+mod save;
+mod skins;
+mod stats;
+mod traits;
+mod tuning;
 mod utils;
+use achievements::{Achievements, TOAST_TIME};
 use asset_bundle::*;
+pub use asset_bundle::{show_asset_errors, AssetError};
+use game_core::*;
+use highscores::{format_date, HighscoreEntry, HighscoreTable, InitialsEntry, INITIALS_LEN};
+use input::*;
+use leaderboard::{Leaderboard, ScoreEntry, TOP_COUNT};
 use level::*;
 use macroquad::{audio::*, prelude::*};
 use macroquad_particles::*;
+use palette::draw_marker;
+use replay::*;
+use save::SaveData;
+use skins::DEFAULT_SKIN;
 use std::rc::Rc;
-use utils::colors::*;
+use tuning::{Difficulty, Tuning};
+use utils::{colors::*, rng::Rng};
 use GameState::*;
 
 pub const GAME_WIDTH: f32 = 1280.0;
@@ -21,46 +44,76 @@ const CHAR_WIDTH: f32 = 120.0;
 const CHAR_HEIGHT: f32 = 120.0;
 const GROUND_WIDTH: f32 = 867.0;
 const GROUND_HEIGHT: f32 = 564.0;
-const SCORE_BASE: f32 = 100.0;
-const SPAWN_DELAY: f32 = 0.2;
-const LEVEL_TIME: f32 = 10.0;
-const CLICK_OFFSET: f32 = 20.0;
 
 pub enum GameState {
     Menu,
     Playing,
     GameOver,
     Paused,
+    Controls,
+    EnterInitials,
+    Stats,
 }
 
 pub struct Game {
     /// Game assets.
     assets: Rc<asset_bundle::AssetBundle>,
-    /// The level struct.
-    level: level::Level,
+    /// The headless game simulation.
+    core: GameCore,
     /// The game state.
     game_state: GameState,
-    /// The score, [level number, total score]
-    score: [f32; 2],
-    // Highscore.
-    highscore: [f32; 2],
-    /// The game over flag.
-    game_over: bool,
+    /// The persisted game data.
+    save: SaveData,
+    /// Action to key/mouse button bindings.
+    input: InputMap,
+    /// The selected [action, slot] on the controls screen.
+    controls_cursor: [usize; 2],
+    /// Whether the controls screen is waiting for a key to bind.
+    rebinding: bool,
+    /// Why the last rebind on the controls screen failed, if it did.
+    rebind_error: Option<String>,
+    /// Whether the target is revealed in practice mode.
+    reveal: bool,
+    /// Highscore table of the selected mode and difficulty.
+    highscores: HighscoreTable,
+    /// The initials being entered for a new highscore.
+    initials: InitialsEntry,
+    /// The rank of the last run on the highscore table, if it made it.
+    new_rank: Option<usize>,
+    /// Global leaderboard client.
+    leaderboard: Leaderboard,
+    /// Achievement definitions and toasts.
+    achievements: Achievements,
     /// The game renderer.
     renderer: renderer::Renderer,
     // Shooting particle effect
     bullet_fx: Emitter,
+    // Position of the last shot
+    bullet_pos: Vec2,
+    // Smoke particle effect when a character spawns
+    smoke_fx: EmittersCache,
+    /// Recording of the current run.
+    recording: Replay,
+    /// The replay being played back, if any.
+    playback: Option<Playback>,
 }
 
 impl Game {
     /// Initializes the game.
-    pub async fn init() -> Game {
+    ///
+    /// Returns the assets that couldn't be loaded if the game can't start without them.
+    pub async fn init() -> Result<Game, Vec<AssetError>> {
         set_pc_assets_folder("assets");
-        macroquad::rand::srand(macroquad::miniquad::date::now() as u64);
+        macroquad::rand::srand(new_seed()); // Only used by the particle effects
         show_mouse(false); // Hide the mouse cursor
 
-        let assets = Rc::new(asset_bundle::AssetBundle::load().await.unwrap()); // Load game assets
-        let level = Level::init(&assets);
+        let (assets, warnings) = asset_bundle::AssetBundle::load().await?; // Load game assets
+        for warning in &warnings {
+            eprintln!("{}", warning);
+        }
+        let assets = Rc::new(assets);
+        let tuning = Rc::new(Tuning::load().await);
+        let achievements = Achievements::load().await;
 
         // Shooting particle effect.
         let bullet_fx = Emitter::new(EmitterConfig {
@@ -92,44 +145,129 @@ impl Game {
             ..Default::default()
         });
 
+        // Smoke particle effect when spawning.
+        let smoke_fx = EmittersCache::new(EmitterConfig {
+            emission_shape: EmissionShape::Sphere { radius: 50.0 },
+            one_shot: true,
+            lifetime: 0.5,
+            explosiveness: 1.0,
+            amount: 150,
+            shape: ParticleShape::Circle { subdivisions: 10 },
+            emitting: true,
+            initial_direction: vec2(0.0, -1.0),
+            initial_direction_spread: 6.0,
+            initial_velocity: 310.0,
+            initial_velocity_randomness: 0.6,
+            linear_accel: -7.5,
+            size: 10.0,
+            size_randomness: 3.0,
+            size_curve: Some(Curve {
+                points: vec![(0.005, 1.48), (0.255, 1.08), (1.0, 0.12)],
+                interpolation: Interpolation::Linear,
+                resolution: 30,
+            }),
+            blend_mode: BlendMode::Additive,
+            colors_curve: ColorCurve {
+                start: Color::new(1.0, 1.0, 1.0, 1.0),
+                mid: Color::new(0.8, 0.8, 0.8, 0.5),
+                end: Color::new(0.5, 0.5, 0.5, 0.5),
+            },
+            gravity: vec2(0.0, -500.0),
+            post_processing: Some(PostProcessing),
+            ..Default::default()
+        });
+
+        let save = SaveData::load();
+        let input = InputMap::from_saved(&save.bindings);
+        let initials = InitialsEntry::new(&save.initials);
+        assets.set_skin(&save.skin);
+        assets.set_palette(save.palette);
+        assets.set_markers(save.markers);
+        assets.set_word_hints(save.word_hints);
+        let core = GameCore::init(new_seed(), &tuning, assets.part_counts());
         let mut game = Game {
             assets,
-            level,
+            save,
+            core,
             game_state: Menu,
-            score: [0.0, 0.0],
-            highscore: [-1.0, -1.0],
-            game_over: false,
+            input,
+            controls_cursor: [0, 0],
+            rebinding: false,
+            rebind_error: None,
+            reveal: false,
+            highscores: HighscoreTable::default(),
+            initials,
+            new_rank: None,
+            leaderboard: Leaderboard::init(),
+            achievements,
             renderer: renderer::Renderer::init(GAME_WIDTH, GAME_HEIGHT),
             bullet_fx,
+            bullet_pos: Vec2::ZERO,
+            smoke_fx,
+            recording: Replay::new(0, Difficulty::Normal, GameMode::Classic, DEFAULT_SKIN, 0),
+            playback: None,
         };
 
         game.set_menu();
-        game
+        Ok(game)
     }
 
     /// Starts and updates the game based on the game state.
     pub async fn run(&mut self) {
         draw_game_screen!(self, {
+            self.leaderboard.update();
+
             match self.game_state {
                 Menu => {
                     self.menu();
 
-                    if is_key_pressed(KeyCode::Enter) || is_mouse_button_pressed(MouseButton::Left)
-                    {
-                        self.set_level();
+                    if self.input.is_pressed(Action::Confirm) {
+                        self.start_run();
                         play_sound_once(self.assets.menu_in_sound);
-                    } else if is_key_pressed(KeyCode::Escape) {
+                    } else if self.input.is_pressed(Action::Back) {
                         play_sound_once(self.assets.pause_sound);
                         set_sound_volume(self.assets.bg_music, 0.0);
                         self.confirm_quit().await;
                         set_sound_volume(self.assets.bg_music, 1.0);
+                    } else if self.input.is_pressed(Action::SelectUp) {
+                        self.change_mode(-1);
+                        play_sound_once(self.assets.menu_out_sound);
+                    } else if self.input.is_pressed(Action::SelectDown) {
+                        self.change_mode(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::SelectLeft) {
+                        self.change_difficulty(-1);
+                        play_sound_once(self.assets.menu_out_sound);
+                    } else if self.input.is_pressed(Action::SelectRight) {
+                        self.change_difficulty(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::Controls) {
+                        self.set_controls();
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::Stats) {
+                        self.game_state = Stats;
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::ChangeSkin)
+                        && self.assets.skins.len() > 1
+                    {
+                        self.change_skin(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::ChangePalette) {
+                        self.change_palette(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::ToggleMarkers) {
+                        self.toggle_markers();
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::SwitchHints) {
+                        self.toggle_word_hints();
+                        play_sound_once(self.assets.menu_in_sound);
                     }
                 }
 
                 Playing => {
                     self.playing().await;
 
-                    if is_key_pressed(KeyCode::Escape) {
+                    if self.input.is_pressed(Action::Pause) {
                         self.game_state = Paused;
                         play_sound_once(self.assets.pause_sound);
                         set_sound_volume(self.assets.bg_music, 0.0);
@@ -139,13 +277,11 @@ impl Game {
                 GameOver => {
                     self.game_over();
 
-                    if is_key_pressed(KeyCode::Enter) || is_mouse_button_pressed(MouseButton::Left)
-                    {
-                        self.score = [0.0, 0.0];
-                        self.set_level();
+                    if self.input.is_pressed(Action::Restart) {
+                        self.start_run();
                         play_sound_once(self.assets.menu_in_sound);
                         utils::sound::play_sound_looped(self.assets.bg_music, 0.25);
-                    } else if is_key_pressed(KeyCode::Escape) {
+                    } else if self.input.is_pressed(Action::Back) {
                         self.set_menu();
                         play_sound_once(self.assets.menu_out_sound);
                     }
@@ -154,30 +290,95 @@ impl Game {
                 Paused => {
                     self.paused();
 
-                    if is_key_pressed(KeyCode::Escape) {
+                    if self.input.is_pressed(Action::Back) {
                         self.set_menu();
                         play_sound_once(self.assets.menu_out_sound);
-                    } else if is_key_pressed(KeyCode::Enter)
-                        || is_mouse_button_pressed(MouseButton::Left)
-                    {
+                    } else if self.input.is_pressed(Action::Confirm) {
                         self.game_state = Playing;
-                        self.level.timer_on = true;
                         play_sound_once(self.assets.menu_in_sound);
                         set_sound_volume(self.assets.bg_music, 0.25);
                     }
                 }
+
+                Stats => {
+                    self.stats();
+
+                    if self.input.is_pressed(Action::Back) || self.input.is_pressed(Action::Stats) {
+                        self.game_state = Menu;
+                        play_sound_once(self.assets.menu_out_sound);
+                    }
+                }
+
+                EnterInitials => {
+                    self.enter_initials();
+
+                    // Typed letters take priority over the actions bound to letter keys
+                    if let Some(c) = get_char_pressed().filter(|c| c.is_ascii_alphabetic()) {
+                        self.initials.type_char(c);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::Confirm) {
+                        self.save_initials();
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::SelectUp) {
+                        self.initials.change_letter(-1);
+                        play_sound_once(self.assets.menu_out_sound);
+                    } else if self.input.is_pressed(Action::SelectDown) {
+                        self.initials.change_letter(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    } else if self.input.is_pressed(Action::SelectLeft)
+                        || self.input.is_pressed(Action::Erase)
+                    {
+                        self.initials.move_cursor(-1);
+                        play_sound_once(self.assets.menu_out_sound);
+                    } else if self.input.is_pressed(Action::SelectRight) {
+                        self.initials.move_cursor(1);
+                        play_sound_once(self.assets.menu_in_sound);
+                    }
+                }
+
+                Controls => {
+                    self.controls();
+
+                    if self.rebinding {
+                        if is_key_pressed(CANCEL_KEY) {
+                            self.rebinding = false;
+                            play_sound_once(self.assets.menu_out_sound);
+                        } else if let Some(binding) = Binding::pressed() {
+                            let [action, slot] = self.controls_cursor;
+                            self.rebinding = false;
+                            match self.input.bind(Action::ALL[action], slot, binding) {
+                                Ok(()) => {
+                                    self.save_bindings();
+                                    play_sound_once(self.assets.menu_in_sound);
+                                }
+                                Err(error) => {
+                                    self.rebind_error = Some(error);
+                                    play_sound_once(self.assets.menu_out_sound);
+                                }
+                            }
+                        }
+                    } else {
+                        let clicked = self.move_controls_cursor();
+
+                        if self.input.is_pressed(Action::Confirm) && !clicked {
+                            self.rebind_error = None;
+                            self.rebinding = true;
+                            play_sound_once(self.assets.pause_sound);
+                        } else if self.input.is_pressed(Action::Back) {
+                            self.game_state = Menu;
+                            play_sound_once(self.assets.menu_out_sound);
+                        } else if self.input.is_pressed(Action::Erase) {
+                            self.input = InputMap::default_bindings();
+                            self.rebind_error = None;
+                            self.save_bindings();
+                            play_sound_once(self.assets.menu_out_sound);
+                        }
+                    }
+                }
             }
         })
     }
 
-    /// Increments the score.
-    fn add_score(&mut self) {
-        let level_bonus = (SCORE_BASE / 10.0) * self.score[0];
-        let time_bonus = (SCORE_BASE + level_bonus) * (self.level.timer / LEVEL_TIME);
-        self.score[1] += SCORE_BASE + level_bonus + time_bonus;
-        self.score[0] += 1.0;
-    }
-
     /// Draws a crosshair cursor at the mouse position.
     fn draw_cursor(&mut self, width: f32, height: f32) {
         let (mouse_x, mouse_y) = mouse_position();
@@ -193,36 +394,90 @@ impl Game {
         );
     }
 
-    /// Checks if the mouse clicked on a character.
+    /// Starts a new run and starts recording it.
+    fn start_run(&mut self) {
+        let seed = new_seed();
+        self.core.reset(seed);
+        let skin = &self.assets.skin().name;
+        let tuning = self.core.tuning.fingerprint();
+        self.recording = Replay::new(seed, self.core.difficulty, self.core.mode, skin, tuning);
+        self.playback = None;
+        if self.core.mode.keeps_score() {
+            self.save.stats.start_run();
+            self.achievements.start_run();
+        }
+        self.set_level();
+    }
+
+    /// Starts playing back a recorded run.
+    ///
+    /// Fails if the skin the run was played with isn't installed, or if the tuning values
+    /// changed since, either would play the run back differently.
+    pub fn start_replay(&mut self, replay: Replay) -> Result<(), String> {
+        let tuning = self.core.tuning.fingerprint();
+        if replay.tuning != tuning {
+            return Err("the replay was recorded with different tuning values".to_string());
+        }
+        let skin = if replay.skin.is_empty() {
+            DEFAULT_SKIN
+        } else {
+            &replay.skin
+        };
+        if !self.use_skin(skin) {
+            return Err(format!("the replay needs the \"{}\" skin pack", skin));
+        }
+
+        self.core.difficulty = replay.difficulty;
+        self.core.mode = replay.mode;
+        self.core.reset(replay.seed);
+        self.recording = Replay::new(replay.seed, replay.difficulty, replay.mode, skin, tuning);
+        self.playback = Some(Playback::new(replay));
+        self.set_level();
+        Ok(())
+    }
+
+    /// Loads a replay file and starts playing it back.
     ///
-    /// Returns `Some(true)` if the target character was clicked.
-    /// Returns `Some(false)` if a non-target character was clicked.
-    /// Returns `None` if no character was clicked.
-    fn check_target_click(&mut self) -> Option<bool> {
-        if is_mouse_button_pressed(MouseButton::Left) && self.level.timer_on {
+    /// Prints the error and keeps the game at the menu if the file can't be loaded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn play_replay_file(&mut self, path: &str) {
+        if let Err(e) = Replay::load(path).and_then(|replay| self.start_replay(replay)) {
+            eprintln!("Failed to load replay: {}", e);
+        }
+    }
+
+    /// Collects the player input for this frame.
+    fn read_input(&self) -> Vec<InputEvent> {
+        let mut input = Vec::new();
+        if self.input.is_pressed(Action::Shoot) {
             let (mouse_x, mouse_y) = self.renderer.mouse_position();
+            input.push(InputEvent::Shoot(mouse_x, mouse_y));
+        }
 
-            // Trigger bullet particle effect
-            self.bullet_fx.config.emitting = true;
-            play_sound_once(self.assets.shoot_sound);
-
-            // Check if mouse clicked on a character
-            for character in self.level.crowd.iter_mut() {
-                if character.spawned
-                    && mouse_x >= character.x
-                    && mouse_x <= character.x + CHAR_WIDTH
-                    && mouse_y >= character.y + CLICK_OFFSET
-                    && mouse_y <= character.y + CHAR_HEIGHT
-                {
-                    character.dead = true;
-                    return Some(character.is_target);
-                }
+        // Keyboard targeting
+        let directions = [
+            (Action::SelectUp, Direction::Up),
+            (Action::SelectDown, Direction::Down),
+            (Action::SelectLeft, Direction::Left),
+            (Action::SelectRight, Direction::Right),
+        ];
+        for (action, direction) in directions {
+            if self.input.is_pressed(action) {
+                input.push(InputEvent::Select(direction));
             }
         }
-        None
+        if self.input.is_pressed(Action::ShootSelected) {
+            input.push(InputEvent::ShootSelected);
+        }
+        input
     }
 }
 
+/// Returns a new seed based on the current time.
+fn new_seed() -> u64 {
+    (macroquad::miniquad::date::now() * 1000.0) as u64
+}
+
 // Macros
 #[macro_export]
 /// Macro to draw the game screen.
@@ -235,6 +490,7 @@ macro_rules! draw_game_screen {
 
             $code
 
+            $game.draw_toast();
             $game.renderer.draw();
             $game.draw_cursor(96.0, 96.0);
             next_frame().await
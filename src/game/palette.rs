@@ -0,0 +1,216 @@
+//! Palette
+//!
+//! Color palettes for color vision deficiencies and a high contrast one. A palette
+//! remaps the character tints, by the colors' names in the manifest, and the colors
+//! of the timer bar and the overlays. The pattern markers give each tint a pattern
+//! too, so color is never the only cue.
+
+use super::utils::colors::*;
+use macroquad::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+/// Number of different marker patterns, tints past it reuse them.
+const PATTERN_COUNT: usize = 6;
+
+/// A color palette.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, SerJson, DeJson)]
+pub enum Palette {
+    /// The manifest's tints.
+    #[default]
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+/// The colors of the interface elements of a palette.
+pub struct Theme {
+    /// The timer bar, [plenty of time, a third used up, two thirds used up].
+    pub timer: [Color; 3],
+    /// The reveal overlay square of a trait the character shares with the target.
+    pub matched: Color,
+    /// The reveal overlay square of a trait the character doesn't share with the target.
+    pub missed: Color,
+    /// The reveal overlay outline of the target.
+    pub target: Color,
+    /// The overlay over the menu crowd and the game screens.
+    pub overlay: Color,
+}
+
+impl Palette {
+    /// All the palettes, in menu order.
+    pub const ALL: [Palette; 5] = [
+        Palette::Default,
+        Palette::Deuteranopia,
+        Palette::Protanopia,
+        Palette::Tritanopia,
+        Palette::HighContrast,
+    ];
+
+    /// Returns the palette's display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::Protanopia => "Protanopia",
+            Palette::Tritanopia => "Tritanopia",
+            Palette::HighContrast => "High contrast",
+        }
+    }
+
+    /// Returns the next (`step` > 0) or previous (`step` < 0) palette, wrapping around.
+    pub fn step(self, step: isize) -> Palette {
+        let i = Palette::ALL.iter().position(|p| *p == self).unwrap() as isize;
+        let i = (i + step).rem_euclid(Palette::ALL.len() as isize);
+        Palette::ALL[i as usize]
+    }
+
+    /// Returns the palette's replacement for the color with the given manifest name,
+    /// `color` in the default palette or for a color the palette doesn't remap.
+    pub fn tint(self, name: &str, color: Color) -> Color {
+        self.remap(name).map_or(color, |(tint, _)| tint)
+    }
+
+    /// Returns the name of the palette's replacement for the color with the given
+    /// manifest name in the word hints, `None` if it isn't remapped.
+    pub fn color_name(self, name: &str) -> Option<&'static str> {
+        self.remap(name).map(|(_, name)| name)
+    }
+
+    /// Returns the palette's tint and its name for the color with the given manifest name.
+    fn remap(self, name: &str) -> Option<(Color, &'static str)> {
+        let remaps = match self {
+            Palette::Default => return None,
+            Palette::Deuteranopia => [
+                ("blue", color_u8!(0, 114, 178, 255), "blue"),
+                ("red", color_u8!(213, 94, 0, 255), "red"),
+                ("orange", color_u8!(240, 228, 66, 255), "yellow"),
+                ("green", color_u8!(86, 180, 233, 255), "sky blue"),
+                ("yellow", color_u8!(204, 121, 167, 255), "pink"),
+            ],
+            Palette::Protanopia => [
+                ("blue", color_u8!(0, 114, 178, 255), "blue"),
+                ("red", color_u8!(230, 159, 0, 255), "orange"),
+                ("orange", color_u8!(225, 225, 225, 255), "white"),
+                ("green", color_u8!(86, 180, 233, 255), "sky blue"),
+                ("yellow", color_u8!(204, 121, 167, 255), "pink"),
+            ],
+            Palette::Tritanopia => [
+                ("blue", color_u8!(0, 150, 150, 255), "teal"),
+                ("red", color_u8!(220, 50, 32, 255), "red"),
+                ("orange", color_u8!(255, 160, 200, 255), "pink"),
+                ("green", color_u8!(110, 110, 110, 255), "grey"),
+                ("yellow", color_u8!(240, 240, 240, 255), "white"),
+            ],
+            Palette::HighContrast => [
+                ("blue", color_u8!(0, 230, 255, 255), "cyan"),
+                ("red", color_u8!(255, 40, 40, 255), "red"),
+                ("orange", color_u8!(255, 0, 220, 255), "magenta"),
+                ("green", color_u8!(0, 255, 0, 255), "green"),
+                ("yellow", color_u8!(255, 235, 0, 255), "yellow"),
+            ],
+        };
+        remaps
+            .into_iter()
+            .find(|(color, ..)| *color == name)
+            .map(|(_, tint, name)| (tint, name))
+    }
+
+    /// Returns the colors of the interface elements.
+    pub fn theme(self) -> Theme {
+        match self {
+            Palette::Default => Theme {
+                timer: [COLOR_GREEN, COLOR_YELLOW, COLOR_RED],
+                matched: COLOR_GREEN,
+                missed: COLOR_RED,
+                target: COLOR_RED,
+                overlay: OVERLAY_PURPLE,
+            },
+            // Blue and orange instead of green and red
+            Palette::Deuteranopia | Palette::Protanopia => Theme {
+                timer: [
+                    color_u8!(86, 180, 233, 255),
+                    color_u8!(240, 228, 66, 255),
+                    color_u8!(230, 159, 0, 255),
+                ],
+                matched: color_u8!(86, 180, 233, 255),
+                missed: color_u8!(230, 159, 0, 255),
+                target: color_u8!(230, 159, 0, 255),
+                overlay: OVERLAY_PURPLE,
+            },
+            Palette::Tritanopia => Theme {
+                timer: [
+                    color_u8!(0, 150, 150, 255),
+                    color_u8!(255, 160, 200, 255),
+                    color_u8!(220, 50, 32, 255),
+                ],
+                matched: color_u8!(0, 150, 150, 255),
+                missed: color_u8!(220, 50, 32, 255),
+                target: color_u8!(220, 50, 32, 255),
+                overlay: OVERLAY_PURPLE,
+            },
+            Palette::HighContrast => Theme {
+                timer: [
+                    WHITE,
+                    color_u8!(255, 235, 0, 255),
+                    color_u8!(255, 40, 40, 255),
+                ],
+                matched: WHITE,
+                missed: color_u8!(255, 40, 40, 255),
+                target: color_u8!(255, 235, 0, 255),
+                overlay: color_u8!(0, 0, 0, 230),
+            },
+        }
+    }
+}
+
+/// Draws a square of the given color variant's pattern, filled with `color`.
+pub fn draw_marker(variant: usize, x: f32, y: f32, size: f32, color: Color) {
+    draw_rectangle(x, y, size, size, color);
+    let (step, thickness) = (size / 4.0, (size / 10.0).max(1.0));
+    match variant % PATTERN_COUNT {
+        // Solid
+        0 => {}
+        // Horizontal stripes
+        1 => {
+            for i in 1..4 {
+                let y = y + i as f32 * step;
+                draw_line(x, y, x + size, y, thickness, BG_PURPLE);
+            }
+        }
+        // Vertical stripes
+        2 => {
+            for i in 1..4 {
+                let x = x + i as f32 * step;
+                draw_line(x, y, x, y + size, thickness, BG_PURPLE);
+            }
+        }
+        // Diagonal stripes
+        3 => {
+            // Lines where u + v = k, u and v across and down the square
+            for i in 1..4 {
+                let k = i as f32 * size / 2.0;
+                let (u, v) = if k <= size {
+                    (0.0, k)
+                } else {
+                    (k - size, size)
+                };
+                draw_line(x + u, y + v, x + v, y + u, thickness, BG_PURPLE);
+            }
+        }
+        // Dots
+        4 => {
+            for (i, j) in [(1, 1), (3, 1), (2, 2), (1, 3), (3, 3)] {
+                let (cx, cy) = (x + i as f32 * step, y + j as f32 * step);
+                draw_circle(cx, cy, thickness, BG_PURPLE);
+            }
+        }
+        // Cross
+        _ => {
+            draw_line(x, y, x + size, y + size, thickness, BG_PURPLE);
+            draw_line(x + size, y, x, y + size, thickness, BG_PURPLE);
+        }
+    }
+    draw_rectangle_lines(x, y, size, size, 2.0, BG_PURPLE);
+}
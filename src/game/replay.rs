@@ -0,0 +1,204 @@
+//! Replay
+//!
+//! Records a run as its seed plus the input fed into the game core every frame,
+//! so the run can be played back exactly.
+
+use super::{
+    game_core::{GameMode, InputEvent},
+    tuning::Difficulty,
+};
+use nanoserde::{DeJson, SerJson};
+
+/// Version of the replay file format. Bump when the format or the game rules change.
+pub const REPLAY_VERSION: u32 = 7;
+/// Directory the replays are saved to.
+pub const REPLAY_DIR: &str = "replays";
+
+/// A single game core step.
+#[derive(Clone, Debug, SerJson, DeJson)]
+pub struct ReplayFrame {
+    /// Time since the start of the run, in seconds.
+    pub time: f32,
+    /// The step's delta time.
+    pub dt: f32,
+    /// The input fed into the step.
+    pub input: Vec<InputEvent>,
+}
+
+/// A recorded run.
+#[derive(Clone, Debug, SerJson, DeJson)]
+pub struct Replay {
+    /// Replay file format version.
+    pub version: u32,
+    /// The seed the run was started with.
+    pub seed: u64,
+    /// The difficulty of the run.
+    #[nserde(default)]
+    pub difficulty: Difficulty,
+    /// The game mode of the run.
+    #[nserde(default)]
+    pub mode: GameMode,
+    /// The skin the run was played with, the default one if empty. Its number of part
+    /// variants changes the generated crowds.
+    #[nserde(default)]
+    pub skin: String,
+    /// Fingerprint of the tuning values the run was played with, see `Tuning::fingerprint`.
+    pub tuning: u64,
+    /// The final score, [level number, total score]
+    pub score: [f32; 2],
+    /// Every step of the run, in order.
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    /// Starts recording a new run with the given seed, difficulty, mode, skin and tuning
+    /// fingerprint.
+    pub fn new(
+        seed: u64,
+        difficulty: Difficulty,
+        mode: GameMode,
+        skin: &str,
+        tuning: u64,
+    ) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            difficulty,
+            mode,
+            skin: skin.to_string(),
+            tuning,
+            score: [0.0, 0.0],
+            frames: Vec::new(),
+        }
+    }
+
+    /// Records a game core step.
+    pub fn push_frame(&mut self, dt: f32, input: &[InputEvent]) {
+        let time = self
+            .frames
+            .last()
+            .map_or(0.0, |frame| frame.time + frame.dt);
+        self.frames.push(ReplayFrame {
+            time,
+            dt,
+            input: input.to_vec(),
+        });
+    }
+
+    /// Parses a replay from JSON, checking its version.
+    pub fn from_json(json: &str) -> Result<Replay, String> {
+        let replay = Replay::deserialize_json(json).map_err(|e| e.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "unsupported replay version {} (expected {})",
+                replay.version, REPLAY_VERSION
+            ));
+        }
+        Ok(replay)
+    }
+
+    /// Loads a replay file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Replay::from_json(&json).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Saves the replay to `REPLAY_DIR`, named after its seed. Returns the file path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> std::io::Result<String> {
+        std::fs::create_dir_all(REPLAY_DIR)?;
+        let path = format!("{}/{}.json", REPLAY_DIR, self.seed);
+        std::fs::write(&path, self.serialize_json())?;
+        Ok(path)
+    }
+}
+
+/// Plays back a replay one frame at a time.
+pub struct Playback {
+    pub replay: Replay,
+    frame: usize,
+}
+
+impl Playback {
+    /// Starts playing back the replay from its first frame.
+    pub fn new(replay: Replay) -> Playback {
+        Playback { replay, frame: 0 }
+    }
+
+    /// Returns the next recorded frame, or `None` when the replay is over.
+    pub fn next_frame(&mut self) -> Option<&ReplayFrame> {
+        let frame = self.replay.frames.get(self.frame)?;
+        self.frame += 1;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_core::{
+        tests::{indices, start},
+        CoreEvent, GameCore,
+    };
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// Steps the core with the frame's input, setting the next level when one is cleared.
+    fn step(core: &mut GameCore, dt: f32, input: &[InputEvent]) -> Vec<CoreEvent> {
+        let events = core.step(dt, input);
+        if events.contains(&CoreEvent::LevelCleared) {
+            core.set_level();
+        }
+        events
+    }
+
+    #[test]
+    fn a_recorded_run_plays_back_the_same() {
+        let seed = 7;
+        let mut core = start(seed, GameMode::Classic);
+        let mut replay = Replay::new(
+            seed,
+            core.difficulty,
+            core.mode,
+            "default",
+            core.tuning.fingerprint(),
+        );
+        let mut recorded = Vec::new();
+        // Shoot a target every half second for a few levels, then a wrong character
+        for frame in 0..6000 {
+            let mut input = Vec::new();
+            if core.level.timer_on && frame % 30 == 0 {
+                let cleared = recorded.iter().filter(|&e| *e == CoreEvent::LevelCleared);
+                let targets = if cleared.count() >= 3 {
+                    indices(&core, false)
+                } else {
+                    indices(&core, true)
+                };
+                let alive = targets.into_iter().find(|&i| !core.level.crowd[i].dead);
+                if let Some(index) = alive {
+                    let (x, y) = core.level.crowd[index].center(core.tuning.click_offset);
+                    input.push(InputEvent::Shoot(x, y));
+                }
+            }
+            replay.push_frame(DT, &input);
+            recorded.extend(step(&mut core, DT, &input));
+            if core.game_over {
+                break;
+            }
+        }
+        assert!(core.game_over);
+        assert!(recorded.contains(&CoreEvent::LevelCleared));
+        replay.score = core.score;
+
+        let replay = Replay::from_json(&replay.serialize_json()).unwrap();
+        let mut core = start(replay.seed, replay.mode);
+        let mut played = Vec::new();
+        let mut playback = Playback::new(replay);
+        while let Some(frame) = playback.next_frame() {
+            played.extend(step(&mut core, frame.dt, &frame.input));
+        }
+        assert_eq!(played, recorded);
+        assert_eq!(core.score, playback.replay.score);
+    }
+}
@@ -0,0 +1,270 @@
+//! Save
+//!
+//! Everything the game persists, kept as versioned JSON in a single `quad_storage` key.
+//! The highscore saved before this format is migrated on first load, and a save that
+//! can't be read is backed up to another key before falling back to the previous backup.
+
+use super::{
+    game_core::GameMode,
+    highscores::{HighscoreEntry, HighscoreTable},
+    palette::Palette,
+    stats::Stats,
+    tuning::Difficulty,
+};
+use nanoserde::{DeJson, SerJson};
+use std::collections::HashMap;
+
+/// Version of the save format. Bump and migrate in `SaveData::upgrade` when the format changes.
+///
+/// 2: mistakes by trait category name instead of by trait index.
+pub const SAVE_VERSION: u32 = 2;
+/// Storage key of the save.
+const SAVE_KEY: &str = "save";
+/// Storage key a save that can't be read is moved to.
+const BACKUP_KEY: &str = "save_backup";
+
+/// The persisted game data.
+#[derive(Clone, Debug, SerJson, DeJson)]
+pub struct SaveData {
+    /// Save format version.
+    pub version: u32,
+    /// The last selected difficulty.
+    #[nserde(default)]
+    pub difficulty: Difficulty,
+    /// The last selected game mode.
+    #[nserde(default)]
+    pub mode: GameMode,
+    /// Action name to binding names, empty for the default bindings.
+    #[nserde(default)]
+    pub bindings: HashMap<String, Vec<String>>,
+    /// The highscore tables, by `table_key`.
+    #[nserde(default)]
+    pub highscores: HashMap<String, HighscoreTable>,
+    /// The last initials entered, empty if none yet.
+    #[nserde(default)]
+    pub initials: String,
+    /// Lifetime player statistics.
+    #[nserde(default)]
+    pub stats: Stats,
+    /// Ids of the unlocked achievements.
+    #[nserde(default)]
+    pub achievements: Vec<String>,
+    /// Name of the selected skin, empty for the default one.
+    #[nserde(default)]
+    pub skin: String,
+    /// The selected color palette.
+    #[nserde(default)]
+    pub palette: Palette,
+    /// Whether the character tints are drawn with pattern markers.
+    #[nserde(default)]
+    pub markers: bool,
+    /// Whether the hints describe the target in words instead of pictures.
+    #[nserde(default)]
+    pub word_hints: bool,
+}
+
+impl Default for SaveData {
+    fn default() -> SaveData {
+        SaveData {
+            version: SAVE_VERSION,
+            difficulty: Difficulty::default(),
+            mode: GameMode::default(),
+            bindings: HashMap::new(),
+            highscores: HashMap::new(),
+            initials: String::new(),
+            stats: Stats::default(),
+            achievements: Vec::new(),
+            skin: String::new(),
+            palette: Palette::default(),
+            markers: false,
+            word_hints: false,
+        }
+    }
+}
+
+impl SaveData {
+    /// Loads the save from storage.
+    ///
+    /// Migrates the old storage keys if there's no save yet. If the save can't be read,
+    /// it's backed up to `save_backup` and the backup it replaces is played with instead,
+    /// or a fresh save if that can't be read either.
+    pub fn load() -> SaveData {
+        let storage = &mut quad_storage::STORAGE.lock().unwrap();
+        let json = match storage.get(SAVE_KEY) {
+            Some(json) => json,
+            None => {
+                let level = storage.get("highscore_level");
+                let total = storage.get("highscore_total");
+                let save = SaveData::from_legacy(level.as_deref(), total.as_deref());
+                storage.remove("highscore_level");
+                storage.remove("highscore_total");
+                storage.set(SAVE_KEY, &save.serialize_json());
+                return save;
+            }
+        };
+
+        let backup = storage.get(BACKUP_KEY);
+        let (save, error) = SaveData::recover(&json, backup.as_deref());
+        if let Some(error) = error {
+            eprintln!(
+                "Failed to load the save ({}), it was backed up to \"{}\"",
+                error, BACKUP_KEY
+            );
+            storage.set(BACKUP_KEY, &json);
+            storage.set(SAVE_KEY, &save.serialize_json());
+        }
+        save
+    }
+
+    /// Parses a save, migrating it if it's of an older version. Saves of a newer version
+    /// are refused, rather than losing what this version doesn't know about.
+    pub fn parse(json: &str) -> Result<SaveData, String> {
+        let save = SaveData::deserialize_json(json).map_err(|e| e.to_string())?;
+        if save.version > SAVE_VERSION {
+            return Err(format!("unsupported save version {}", save.version));
+        }
+        Ok(save.upgrade())
+    }
+
+    /// Picks the save to play with out of the saved and the backed up JSON: the saved one
+    /// if it can be parsed, else the backup, else a fresh save. Returns the error the
+    /// saved one couldn't be parsed with, if it couldn't.
+    pub fn recover(json: &str, backup: Option<&str>) -> (SaveData, Option<String>) {
+        match SaveData::parse(json) {
+            Ok(save) => (save, None),
+            Err(error) => {
+                let save = backup
+                    .and_then(|backup| SaveData::parse(backup).ok())
+                    .unwrap_or_default();
+                (save, Some(error))
+            }
+        }
+    }
+
+    /// Migrates a save of an older version to the current one.
+    fn upgrade(mut self) -> SaveData {
+        if self.version < 2 {
+            self.stats.migrate_mistakes();
+        }
+        self.version = SAVE_VERSION;
+        self
+    }
+
+    /// Saves to storage.
+    pub fn save(&self) {
+        let storage = &mut quad_storage::STORAGE.lock().unwrap();
+        storage.set(SAVE_KEY, &self.serialize_json());
+    }
+
+    /// Returns the highscore table of the given mode and difficulty.
+    pub fn highscores(&self, mode: GameMode, difficulty: Difficulty) -> HighscoreTable {
+        self.highscores
+            .get(&table_key(mode, difficulty))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Replaces the highscore table of the given mode and difficulty.
+    pub fn set_highscores(
+        &mut self,
+        mode: GameMode,
+        difficulty: Difficulty,
+        table: HighscoreTable,
+    ) {
+        self.highscores.insert(table_key(mode, difficulty), table);
+    }
+
+    /// Builds a save from the values of the highscore keys used before the save format.
+    /// The highscore is kept as a Classic Normal one if it can be read.
+    pub fn from_legacy(level: Option<&str>, total: Option<&str>) -> SaveData {
+        let mut save = SaveData::default();
+
+        let highscore = || {
+            Some(HighscoreEntry {
+                score: total?.parse().ok()?,
+                level: level?.parse().ok()?,
+                date: 0.0,
+                mode: GameMode::Classic,
+                initials: "???".to_string(),
+            })
+        };
+        if let Some(entry) = highscore() {
+            let table = HighscoreTable {
+                entries: vec![entry],
+            };
+            save.set_highscores(GameMode::Classic, Difficulty::Normal, table);
+        }
+        save
+    }
+}
+
+/// Returns the key of the highscore table of the given mode and difficulty.
+fn table_key(mode: GameMode, difficulty: Difficulty) -> String {
+    format!("{}/{}", mode.name(), difficulty.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the JSON of a default save with the given version.
+    fn json_of_version(version: u32) -> String {
+        let save = SaveData {
+            version,
+            ..SaveData::default()
+        };
+        save.serialize_json()
+    }
+
+    #[test]
+    fn the_legacy_highscore_becomes_a_classic_normal_one() {
+        let save = SaveData::from_legacy(Some("7"), Some("1234.5"));
+        assert_eq!(save.version, SAVE_VERSION);
+        let table = save.highscores(GameMode::Classic, Difficulty::Normal);
+        assert_eq!(table.entries.len(), 1);
+        assert_eq!(table.entries[0].level, 7.0);
+        assert_eq!(table.entries[0].score, 1234.5);
+
+        let save = SaveData::from_legacy(None, Some("not a number"));
+        assert!(save.highscores.is_empty());
+    }
+
+    #[test]
+    fn a_version_1_save_is_upgraded() {
+        let json = json_of_version(1).replace(
+            "\"best_streak\":0",
+            "\"best_streak\":0,\"mistakes_by_trait\":[2,0,1,0,0]",
+        );
+        let save = SaveData::parse(&json).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        let mistakes = &save.stats.mistakes_by_category;
+        assert_eq!(mistakes.len(), 2);
+        assert_eq!(mistakes["Arms"], 2);
+        assert_eq!(mistakes["Face"], 1);
+    }
+
+    #[test]
+    fn a_corrupt_save_falls_back_to_the_backup() {
+        let backup = SaveData {
+            initials: "ABC".to_string(),
+            ..SaveData::default()
+        };
+        let (save, error) = SaveData::recover("{\"version\":", Some(&backup.serialize_json()));
+        assert!(error.is_some());
+        assert_eq!(save.initials, "ABC");
+
+        let (save, error) = SaveData::recover("{\"version\":", Some("also corrupt"));
+        assert!(error.is_some());
+        assert!(save.initials.is_empty());
+    }
+
+    #[test]
+    fn a_save_of_a_newer_version_is_refused() {
+        let json = json_of_version(SAVE_VERSION + 1);
+        assert!(SaveData::parse(&json).is_err());
+
+        let (save, error) = SaveData::recover(&json, None);
+        assert!(error.unwrap().contains("version"));
+        assert_eq!(save.version, SAVE_VERSION);
+    }
+}
@@ -0,0 +1,206 @@
+//! Skins
+//!
+//! Sets of character part sprites. The default skin is made of the trait variants in
+//! the asset manifest, skin packs are folders in `mods/skins` with a `skin.ron` file
+//! listing their sprites, which either replace or are added to the default variants.
+
+use super::asset_bundle::{decode_image, pack_atlas, AssetError, PartEntry};
+use super::traits::TraitRegistry;
+use macroquad::prelude::*;
+use nanoserde::DeRon;
+
+/// Directory the skin packs are loaded from.
+pub const SKINS_DIR: &str = "mods/skins";
+/// Name of the skin made of the manifest's parts.
+pub const DEFAULT_SKIN: &str = "Default";
+
+/// A loaded skin pack's name, and the images and names of each trait category.
+#[cfg(not(target_arch = "wasm32"))]
+type LoadedPack = (String, Vec<Vec<Image>>, Vec<Vec<String>>);
+
+/// How the parts of a skin pack are combined with the default parts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, DeRon)]
+pub enum SkinMode {
+    /// The listed parts replace the default ones, the others are kept.
+    #[default]
+    Replace,
+    /// The listed variants are added to the default ones.
+    Add,
+}
+
+/// The `skin.ron` file of a skin pack.
+#[derive(Clone, Debug, DeRon)]
+pub struct SkinMeta {
+    /// Name shown on the menu.
+    pub name: String,
+    #[nserde(default)]
+    pub mode: SkinMode,
+    /// The pack's parts, with paths relative to the pack's folder.
+    pub parts: Vec<PartEntry>,
+}
+
+/// A set of character part sprites, packed into an atlas.
+pub struct Skin {
+    pub name: String,
+    pub atlas: Texture2D,
+    /// Where each variant of each trait category is in the atlas, in trait order.
+    pub rects: Vec<Vec<Rect>>,
+    /// The name of each variant of each trait category in the word hints, in trait order.
+    pub names: Vec<Vec<String>>,
+}
+
+impl Skin {
+    /// Packs the images of each trait category into a skin, with the variants' names.
+    /// Fails, with the error reported against `path`, if they don't fit in an atlas.
+    pub fn pack(
+        name: &str,
+        path: &str,
+        images: &[Vec<Image>],
+        names: Vec<Vec<String>>,
+    ) -> Result<Skin, AssetError> {
+        let (atlas, rects) = pack_atlas(path, images)?;
+        let atlas = Texture2D::from_image(&atlas);
+        atlas.set_filter(FilterMode::Nearest);
+        Ok(Skin {
+            name: name.to_string(),
+            atlas,
+            rects,
+            names,
+        })
+    }
+}
+
+/// Loads the skin packs in `SKINS_DIR`, combining their parts with the `default` images
+/// and names of each trait category.
+///
+/// Returns the packs that loaded, sorted by folder name, and an error for each one
+/// that didn't.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_skin_packs(
+    default: &[Vec<Image>],
+    default_names: &[Vec<String>],
+    traits: &TraitRegistry,
+) -> (Vec<Skin>, Vec<AssetError>) {
+    let (mut skins, mut errors) = (Vec::<Skin>::new(), Vec::new());
+    let Ok(entries) = std::fs::read_dir(SKINS_DIR) else {
+        return (skins, errors); // No mods
+    };
+    let mut dirs: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+
+    for dir in dirs {
+        match load_skin_pack(&dir, default, default_names, traits) {
+            Ok((name, ..)) if name == DEFAULT_SKIN || skins.iter().any(|s| s.name == name) => {
+                let cause = format!("skin name \"{}\" is taken, pack skipped", name);
+                errors.push(AssetError::new(&dir.display().to_string(), cause));
+            }
+            Ok((name, images, names)) => {
+                let path = dir.join("skin.ron").display().to_string();
+                match Skin::pack(&name, &path, &images, names) {
+                    Ok(skin) => skins.push(skin),
+                    Err(e) => errors.push(AssetError::new(
+                        &e.path,
+                        format!("{}, pack skipped", e.cause),
+                    )),
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    (skins, errors)
+}
+
+/// The browser build can't list folders, so there are no skin packs.
+#[cfg(target_arch = "wasm32")]
+pub fn load_skin_packs(
+    _default: &[Vec<Image>],
+    _default_names: &[Vec<String>],
+    _traits: &TraitRegistry,
+) -> (Vec<Skin>, Vec<AssetError>) {
+    (Vec::new(), Vec::new())
+}
+
+/// Loads the skin pack in the given folder, returning its name and the images and names
+/// of each trait category.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_skin_pack(
+    dir: &std::path::Path,
+    default: &[Vec<Image>],
+    default_names: &[Vec<String>],
+    traits: &TraitRegistry,
+) -> Result<LoadedPack, AssetError> {
+    let meta_path = dir.join("skin.ron").display().to_string();
+    let skipped = |cause: String| AssetError::new(&meta_path, format!("{}, pack skipped", cause));
+    let ron = std::fs::read_to_string(&meta_path).map_err(|e| skipped(e.to_string()))?;
+    let meta = SkinMeta::deserialize_ron(&ron).map_err(|e| skipped(e.to_string()))?;
+
+    let mut images = default.to_vec();
+    let mut names = default_names.to_vec();
+    for part in &meta.parts {
+        let index = traits
+            .index_of(&part.name)
+            .ok_or_else(|| skipped(format!("unknown trait category \"{}\"", part.name)))?;
+        if traits.get(index).is_color() {
+            let cause = format!(
+                "trait category \"{}\" is a color, it has no sprites",
+                part.name
+            );
+            return Err(skipped(cause));
+        }
+        if !part.names.is_empty() && part.names.len() != part.variants.len() {
+            let cause = format!(
+                "trait category \"{}\" has {} names for {} variants",
+                part.name,
+                part.names.len(),
+                part.variants.len()
+            );
+            return Err(skipped(cause));
+        }
+
+        let mut variants = Vec::with_capacity(part.variants.len());
+        for variant in &part.variants {
+            let path = dir.join(variant);
+            let variant_skipped = |cause: String| {
+                AssetError::new(
+                    &path.display().to_string(),
+                    format!("{}, pack skipped", cause),
+                )
+            };
+            let bytes = std::fs::read(&path).map_err(|e| variant_skipped(e.to_string()))?;
+            variants.push(decode_image(&bytes).map_err(variant_skipped)?);
+        }
+        if meta.mode == SkinMode::Replace {
+            images[index].clear();
+            names[index].clear();
+        }
+        let category = traits.get(index);
+        let first = images[index].len();
+        names[index].extend((0..variants.len()).map(|i| match part.names.get(i) {
+            Some(name) => name.clone(),
+            None => category.numbered_name(first + i),
+        }));
+        images[index].extend(variants);
+    }
+
+    let too_few = |i: usize| !traits.get(i).is_color() && images[i].len() < 2;
+    if let Some(index) = (0..traits.len()).find(|&i| too_few(i)) {
+        return Err(skipped(format!(
+            "trait category \"{}\" needs at least 2 variants",
+            traits.get(index).name
+        )));
+    }
+    let no_none = |i: usize| {
+        let category = traits.get(i);
+        !category.is_color() && category.none.is_some_and(|none| none >= images[i].len())
+    };
+    if let Some(index) = (0..traits.len()).find(|&i| no_none(i)) {
+        return Err(skipped(format!(
+            "trait category \"{}\" has no variant for none",
+            traits.get(index).name
+        )));
+    }
+    Ok((meta.name, images, names))
+}
@@ -0,0 +1,186 @@
+//! Stats
+//!
+//! Lifetime player statistics, updated from the game core events and kept with the
+//! save data. Practice runs and replays don't count.
+
+use super::{game_core::*, traits::TraitRegistry};
+use nanoserde::{DeJson, SerJson};
+use std::collections::HashMap;
+
+/// The trait categories before they were listed in the asset manifest, in the order
+/// of the saved `mistakes_by_trait`.
+const LEGACY_TRAIT_NAMES: [&str; 5] = ["Arms", "Body", "Face", "Hat", "Legs"];
+
+/// Lifetime player statistics.
+#[derive(Clone, Debug, Default, SerJson, DeJson)]
+pub struct Stats {
+    pub runs: u32,
+    /// Targets killed.
+    pub kills: u32,
+    pub wrong_kills: u32,
+    /// Levels where the timer ran out.
+    pub timeouts: u32,
+    /// Shots fired, including the ones that hit nobody.
+    pub shots: u32,
+    /// Total time spent finding the killed targets, in seconds.
+    pub kill_time: f32,
+    /// Most targets killed in a row without a mistake.
+    pub best_streak: u32,
+    /// Wrong kills by the clue the killed character didn't fit, by the clue's trait category
+    /// name or "Position".
+    #[nserde(default)]
+    pub mistakes_by_category: HashMap<String, u32>,
+    /// `mistakes_by_category` as saved before trait categories, by `LEGACY_TRAIT_NAMES`.
+    /// Moved over by `migrate_mistakes`.
+    #[nserde(rename = "mistakes_by_trait")]
+    #[nserde(default_with = "Option::default")]
+    legacy_mistakes: Option<Vec<u32>>,
+}
+
+impl Stats {
+    /// Counts a new run.
+    pub fn start_run(&mut self) {
+        self.runs += 1;
+    }
+
+    /// Moves the mistakes saved before trait categories to `mistakes_by_category`.
+    pub fn migrate_mistakes(&mut self) {
+        let legacy = self.legacy_mistakes.take().unwrap_or_default();
+        for (name, count) in LEGACY_TRAIT_NAMES
+            .into_iter()
+            .zip(legacy)
+            .filter(|p| p.1 > 0)
+        {
+            *self
+                .mistakes_by_category
+                .entry(name.to_string())
+                .or_default() += count;
+        }
+    }
+
+    /// Updates the stats with an event from the game core.
+    pub fn record(&mut self, event: CoreEvent, core: &GameCore, traits: &TraitRegistry) {
+        match event {
+            CoreEvent::Shot(..) => self.shots += 1,
+            CoreEvent::TargetKilled(time) => {
+                self.kills += 1;
+                self.kill_time += time;
+                self.best_streak = self.best_streak.max(core.streak);
+            }
+            CoreEvent::WrongKill(index) => {
+                self.wrong_kills += 1;
+
+                // Against the target they were most likely taken for
+                let level = &core.level;
+                let target = &level.targets[level.closest_target(index)];
+                for clue in &target.clues {
+                    if !clue.fits(&level.crowd, index, level.bounds) {
+                        let name = clue.stat_name(traits);
+                        *self.mistakes_by_category.entry(name).or_default() += 1;
+                    }
+                }
+            }
+            CoreEvent::TimeUp => self.timeouts += 1,
+            CoreEvent::Spawned(_) | CoreEvent::LevelCleared => {}
+        }
+    }
+
+    /// Returns the share of the shots that hit a character, from 0 to 1.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots == 0 {
+            return 0.0;
+        }
+        (self.kills + self.wrong_kills) as f32 / self.shots as f32
+    }
+
+    /// Returns the average time it took to find a target, in seconds.
+    pub fn average_kill_time(&self) -> f32 {
+        if self.kills == 0 {
+            return 0.0;
+        }
+        self.kill_time / self.kills as f32
+    }
+
+    /// Returns the name of the hinted trait category with the most mistakes and its
+    /// count, if there were any.
+    pub fn worst_trait(&self) -> Option<(&str, u32)> {
+        self.mistakes_by_category
+            .iter()
+            .filter(|&(_, &count)| count > 0)
+            // Ties go to the first name, the map's order isn't stable
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(name, &count)| (name.as_str(), count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        asset_bundle::Manifest,
+        game_core::tests::{indices, shoot, spawn_crowd, start},
+    };
+
+    /// Returns the trait categories of the asset manifest.
+    fn traits() -> TraitRegistry {
+        let ron = std::fs::read_to_string("assets/manifest.ron").unwrap();
+        TraitRegistry::new(Manifest::from_ron(&ron).unwrap().traits).unwrap()
+    }
+
+    #[test]
+    fn counts_the_events_of_a_run() {
+        let (traits, mut stats) = (traits(), Stats::default());
+        let mut core = start(1, GameMode::Classic);
+        let mut record = |events: Vec<CoreEvent>, core: &GameCore| {
+            for event in events {
+                stats.record(event, core, &traits);
+            }
+        };
+
+        let events = spawn_crowd(&mut core);
+        record(events, &core);
+        let events = core.step(0.0, &[InputEvent::Shoot(-100.0, -100.0)]);
+        record(events, &core);
+        core.step(1.0, &[]);
+        let target = indices(&core, true)[0];
+        let events = shoot(&mut core, target);
+        record(events, &core);
+
+        core.set_level();
+        spawn_crowd(&mut core);
+        let innocent = indices(&core, false)[0];
+        let events = shoot(&mut core, innocent);
+        record(events, &core);
+
+        assert_eq!((stats.shots, stats.kills, stats.wrong_kills), (3, 1, 1));
+        assert_eq!(stats.best_streak, 1);
+        assert_eq!(stats.accuracy(), 2.0 / 3.0);
+        assert!(stats.average_kill_time() > 1.0);
+        // The innocent didn't fit at least one of the target's clues
+        assert!(stats.worst_trait().is_some());
+    }
+
+    #[test]
+    fn counts_timeouts() {
+        let (traits, mut stats) = (traits(), Stats::default());
+        let mut core = start(2, GameMode::Classic);
+        spawn_crowd(&mut core);
+        core.step(core.level.time_limit, &[]);
+        for event in core.step(0.1, &[]) {
+            stats.record(event, &core, &traits);
+        }
+        assert_eq!(stats.timeouts, 1);
+    }
+
+    #[test]
+    fn legacy_mistakes_are_moved_by_category_name() {
+        let mut stats = Stats {
+            legacy_mistakes: Some(vec![0, 3, 0, 0, 1]),
+            ..Stats::default()
+        };
+        stats.migrate_mistakes();
+        assert_eq!(stats.legacy_mistakes, None);
+        assert_eq!(stats.worst_trait(), Some(("Body", 3)));
+        assert_eq!(stats.mistakes_by_category["Legs"], 1);
+    }
+}
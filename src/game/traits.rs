@@ -0,0 +1,223 @@
+//! Traits
+//!
+//! The registry of trait categories (arms, body, hat...) characters are made of, listed
+//! in `assets/manifest.ron`. Crowd generation, the hints and the drawing all go through
+//! the registry, so adding a category to the manifest is enough to add it to the game.
+//!
+//! A color category has colors instead of sprites, a character's color variant is the
+//! tint its tinted sprites are drawn in.
+//!
+//! Each variant has a name for the word hints, put in the category's phrases to say
+//! whether the target has it ("wears {}", "doesn't wear {}").
+
+use super::palette::Palette;
+use macroquad::color::{Color, WHITE};
+use nanoserde::DeRon;
+
+/// A kind of trait characters have one variant of.
+#[derive(Clone, Debug, DeRon)]
+pub struct TraitCategory {
+    /// Name shown in the hints, unique.
+    pub name: String,
+    /// Categories are drawn from the lowest layer up.
+    #[nserde(default)]
+    pub layer: i32,
+    /// Where the sprite is drawn relative to the character, in game pixels.
+    #[nserde(default)]
+    pub offset: (f32, f32),
+    /// Whether the sprite is drawn in the character's tint.
+    #[nserde(default)]
+    pub tint: bool,
+    /// The variant meaning the character has none of the trait, drawn as an empty frame
+    /// in the hints.
+    // Not `default`, its generated code for options trips clippy
+    #[nserde(default_with = "Option::default")]
+    pub none: Option<usize>,
+    /// Paths of the variants' textures, relative to the assets folder.
+    #[nserde(default)]
+    pub variants: Vec<String>,
+    /// The variants' colors as (r, g, b), for a color category instead of `variants`.
+    #[nserde(default)]
+    pub colors: Vec<(u8, u8, u8)>,
+    /// The variants' names in the word hints, "a top hat". The variant for none is
+    /// named after the trait, "a hat".
+    #[nserde(default)]
+    pub names: Vec<String>,
+    /// What having a variant is said as, with `{}` for its name.
+    #[nserde(default_with = "default_phrase")]
+    pub phrase: String,
+    /// What not having a variant is said as, with `{}` for its name.
+    #[nserde(default_with = "default_negative")]
+    pub negative: String,
+}
+
+fn default_phrase() -> String {
+    "has {}".to_string()
+}
+
+fn default_negative() -> String {
+    "doesn't have {}".to_string()
+}
+
+impl TraitCategory {
+    /// Returns `true` if the category's variants are colors rather than sprites.
+    pub fn is_color(&self) -> bool {
+        !self.colors.is_empty()
+    }
+
+    /// Returns the number of variants in the manifest.
+    pub fn variant_count(&self) -> usize {
+        self.variants.len().max(self.colors.len())
+    }
+
+    /// Returns the given variant of a color category as a `Color`.
+    pub fn color(&self, variant: usize) -> Color {
+        let (r, g, b) = self.colors[variant];
+        Color::from_rgba(r, g, b, 255)
+    }
+
+    /// Returns the name of the given variant, made up from the category's name if the
+    /// manifest doesn't name it.
+    pub fn variant_name(&self, variant: usize) -> String {
+        self.names
+            .get(variant)
+            .cloned()
+            .unwrap_or_else(|| self.numbered_name(variant))
+    }
+
+    /// Returns a name for a variant that has none, "hat 3".
+    pub fn numbered_name(&self, variant: usize) -> String {
+        format!("{} {}", self.name.to_lowercase(), variant + 1)
+    }
+
+    /// Returns the phrase saying a character has (`has`) or doesn't have the variant
+    /// with the given name. Having the variant for none is not having the trait.
+    pub fn describe(&self, variant: usize, name: &str, has: bool) -> String {
+        let has = has != (self.none == Some(variant));
+        let phrase = if has { &self.phrase } else { &self.negative };
+        phrase.replace("{}", name)
+    }
+}
+
+/// The trait categories, in trait order.
+pub struct TraitRegistry {
+    pub categories: Vec<TraitCategory>,
+    /// Sprite category indices from the lowest layer to the highest.
+    draw_order: Vec<usize>,
+    /// Index of the color category, if any.
+    color_category: Option<usize>,
+}
+
+impl TraitRegistry {
+    /// Builds the registry, checking the categories with `validate`.
+    pub fn new(categories: Vec<TraitCategory>) -> Result<TraitRegistry, String> {
+        validate(&categories)?;
+        let mut draw_order: Vec<usize> = (0..categories.len())
+            .filter(|&i| !categories[i].is_color())
+            .collect();
+        draw_order.sort_by_key(|&i| categories[i].layer); // Stable, same layers keep their order
+        let color_category = categories.iter().position(TraitCategory::is_color);
+
+        Ok(TraitRegistry {
+            categories,
+            draw_order,
+            color_category,
+        })
+    }
+
+    /// Returns the number of categories.
+    pub fn len(&self) -> usize {
+        self.categories.len()
+    }
+
+    /// Returns the category at the given trait index.
+    pub fn get(&self, index: usize) -> &TraitCategory {
+        &self.categories[index]
+    }
+
+    /// Returns the index of the category with the given name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.categories.iter().position(|c| c.name == name)
+    }
+
+    /// Returns the sprite category indices in the order they should be drawn.
+    pub fn draw_order(&self) -> &[usize] {
+        &self.draw_order
+    }
+
+    /// Returns the color variant of a character with the given traits, if there's a
+    /// color category.
+    pub fn color_variant(&self, traits: &[usize]) -> Option<usize> {
+        self.color_category.map(|i| traits[i])
+    }
+
+    /// Returns the tint of a character with the given traits in the given palette,
+    /// white if there's no color category.
+    pub fn tint(&self, traits: &[usize], palette: Palette) -> Color {
+        self.color_category.map_or(WHITE, |i| {
+            let (category, variant) = (&self.categories[i], traits[i]);
+            palette.tint(&category.variant_name(variant), category.color(variant))
+        })
+    }
+}
+
+/// Checks that there's at least one category, that the names are unique, that each
+/// category has enough variants and a name for each of them, and that there's at most
+/// one color category.
+pub fn validate(categories: &[TraitCategory]) -> Result<(), String> {
+    if categories.is_empty() {
+        return Err("there must be at least one trait category".to_string());
+    }
+    for (i, category) in categories.iter().enumerate() {
+        if categories[..i].iter().any(|c| c.name == category.name) {
+            return Err(format!(
+                "trait category \"{}\" is listed more than once",
+                category.name
+            ));
+        }
+        if !category.variants.is_empty() && !category.colors.is_empty() {
+            return Err(format!(
+                "trait category \"{}\" has both variants and colors",
+                category.name
+            ));
+        }
+        if category.is_color() && categories[..i].iter().any(TraitCategory::is_color) {
+            return Err(format!(
+                "trait category \"{}\" is a second color category",
+                category.name
+            ));
+        }
+        // A hinted trait needs another variant for the rest of the crowd
+        if category.variant_count() < 2 {
+            return Err(format!(
+                "trait category \"{}\" needs at least 2 variants",
+                category.name
+            ));
+        }
+        if category
+            .none
+            .is_some_and(|none| none >= category.variant_count())
+        {
+            return Err(format!(
+                "trait category \"{}\" has no variant {} for none",
+                category.name,
+                category.none.unwrap()
+            ));
+        }
+        if !category.names.is_empty() && category.names.len() != category.variant_count() {
+            return Err(format!(
+                "trait category \"{}\" has {} names for {} variants",
+                category.name,
+                category.names.len(),
+                category.variant_count()
+            ));
+        }
+        if !category.phrase.contains("{}") || !category.negative.contains("{}") {
+            return Err(format!(
+                "trait category \"{}\" has a phrase without {{}} for the name",
+                category.name
+            ));
+        }
+    }
+    Ok(())
+}
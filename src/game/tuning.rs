@@ -0,0 +1,498 @@
+//! Tuning
+//!
+//! Gameplay numbers loaded from `assets/tuning.ron` at startup, so the difficulty
+//! can be changed without recompiling the game.
+
+use super::game_core::GameMode;
+use macroquad::file::load_string;
+use nanoserde::{DeJson, DeRon, DeRonErr, DeRonState, SerJson};
+use std::str::Chars;
+
+/// Path of the tuning file, relative to the assets folder.
+const TUNING_PATH: &str = "tuning.ron";
+/// Upper bound for the crowd size, the ground can't fit many more characters.
+pub const MAX_CROWD_SIZE: f32 = 20.0;
+/// Upper bound for the number of targets, the hints panel can't fit more dossiers.
+pub const MAX_TARGETS: f32 = 3.0;
+
+/// Game difficulty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, SerJson, DeJson)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// All the difficulties, from easiest to hardest.
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    /// Returns the difficulty's display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// Returns the next harder (`step` > 0) or easier (`step` < 0) difficulty, clamped.
+    pub fn step(self, step: isize) -> Difficulty {
+        let i = Difficulty::ALL.iter().position(|d| *d == self).unwrap() as isize;
+        let i = (i + step).clamp(0, Difficulty::ALL.len() as isize - 1);
+        Difficulty::ALL[i as usize]
+    }
+}
+
+/// A value that changes with the level number.
+///
+/// Made of `(level, value)` points, linearly interpolated between points and
+/// clamped to the first and last points outside of them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve(pub Vec<(f32, f32)>);
+
+impl Curve {
+    /// Returns the value of the curve at the given level.
+    pub fn at(&self, level: f32) -> f32 {
+        let points = &self.0;
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if level <= first.0 {
+            return first.1;
+        } else if level >= last.0 {
+            return last.1;
+        }
+
+        let i = points.iter().position(|p| p.0 > level).unwrap();
+        let (x0, y0) = points[i - 1];
+        let (x1, y1) = points[i];
+        y0 + (y1 - y0) * (level - x0) / (x1 - x0)
+    }
+
+    /// Checks that the curve has points in increasing level order with values in `min..=max`.
+    fn validate(&self, name: &str, min: f32, max: f32, errors: &mut Vec<String>) {
+        if self.0.is_empty() {
+            errors.push(format!("{}: needs at least one point", name));
+        }
+        for (i, (level, value)) in self.0.iter().enumerate() {
+            if i > 0 && *level <= self.0[i - 1].0 {
+                errors.push(format!("{}: levels must be increasing", name));
+            }
+            if *level < 0.0 {
+                errors.push(format!("{}: level {} is negative", name, level));
+            }
+            if !(min..=max).contains(value) {
+                errors.push(format!(
+                    "{}: value {} at level {} is not between {} and {}",
+                    name, value, level, min, max
+                ));
+            }
+        }
+    }
+}
+
+impl DeRon for Curve {
+    fn de_ron(s: &mut DeRonState, i: &mut Chars) -> Result<Curve, DeRonErr> {
+        Ok(Curve(Vec::de_ron(s, i)?))
+    }
+}
+
+/// Difficulty preset, applied on top of the tuning values.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub struct Preset {
+    /// Multiplier of the level time.
+    pub level_time: f32,
+    /// Multiplier of how fast the crowd grows past its starting size.
+    pub crowd_growth: f32,
+    /// Number of clues about the target shown in the hints.
+    pub hinted_traits: usize,
+    /// Multiplier of the spawn delays.
+    pub spawn_delay: f32,
+    /// Multiplier of the score.
+    pub score: f32,
+}
+
+impl Preset {
+    /// Checks that the preset values are in range.
+    fn validate(&self, name: &str, errors: &mut Vec<String>) {
+        if self.level_time <= 0.0 {
+            errors.push(format!("{}: level_time must be positive", name));
+        }
+        if self.crowd_growth < 0.0 || self.spawn_delay < 0.0 {
+            errors.push(format!(
+                "{}: crowd_growth and spawn_delay must not be negative",
+                name
+            ));
+        }
+        if self.score < 0.0 {
+            errors.push(format!("{}: score must not be negative", name));
+        }
+        if self.hinted_traits < 1 {
+            errors.push(format!("{}: hinted_traits must be at least 1", name));
+        }
+    }
+}
+
+/// Time attack mode values.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub struct TimeAttack {
+    /// Time on the run-wide clock at the start of the run, in seconds.
+    pub clock: f32,
+    /// Seconds added for each kill, scaled by how much bigger the crowd is than the first one.
+    pub kill_bonus: f32,
+    /// Seconds removed for each wrong kill.
+    pub wrong_kill_penalty: f32,
+}
+
+/// Crowd wandering values.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub struct Wander {
+    /// How fast the characters walk, in pixels per second.
+    pub walk_speed: Curve,
+    /// Shortest time a character stands still between walks, in seconds.
+    pub idle_min: f32,
+    /// Longest time a character stands still between walks, in seconds.
+    pub idle_max: f32,
+    /// Distance under which characters push each other apart, in pixels.
+    pub separation: f32,
+}
+
+/// How often each kind of clue is picked, relative to the others. 0 never picks it.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub struct ClueWeights {
+    /// Has a trait.
+    pub has: f32,
+    /// Doesn't have a trait.
+    pub not: f32,
+    /// Stands next to someone with a trait.
+    pub next_to: f32,
+    /// Is the leftmost, rightmost, furthest back or frontmost of everyone with a trait.
+    pub extreme: f32,
+    /// Stands in a half of the level.
+    pub half: f32,
+}
+
+/// Gameplay tuning values. Anything missing from the file falls back to the default.
+#[derive(Clone, Debug, PartialEq, DeRon)]
+pub struct Tuning {
+    /// Base score for killing a target.
+    #[nserde(default = "100.0")]
+    pub score_base: f32,
+    /// Height of the top of a character (above the head) that can't be clicked.
+    #[nserde(default = "20.0")]
+    pub click_offset: f32,
+    /// Delay between spawning characters, in seconds.
+    #[nserde(default = "0.2")]
+    pub spawn_delay: f32,
+    /// Time to find a target, in seconds. Multiplied by the number of targets.
+    #[nserde(default_with = "default_level_time")]
+    pub level_time: Curve,
+    /// Number of targets in a contract, rounded down.
+    #[nserde(default_with = "default_targets")]
+    pub targets: Curve,
+    /// Number of targets in a contract in Contracts mode, rounded down.
+    #[nserde(default_with = "default_contract_targets")]
+    pub contract_targets: Curve,
+    /// Number of characters in the crowd, rounded down.
+    #[nserde(default_with = "default_crowd_size")]
+    pub crowd_size: Curve,
+    /// Delay before the first character spawns, in seconds.
+    #[nserde(default_with = "default_first_spawn_delay")]
+    pub first_spawn_delay: Curve,
+    /// Easy difficulty preset.
+    #[nserde(default_with = "default_easy")]
+    pub easy: Preset,
+    /// Normal difficulty preset.
+    #[nserde(default_with = "default_normal")]
+    pub normal: Preset,
+    /// Hard difficulty preset.
+    #[nserde(default_with = "default_hard")]
+    pub hard: Preset,
+    /// Time attack mode values.
+    #[nserde(default_with = "default_time_attack")]
+    pub time_attack: TimeAttack,
+    /// Crowd wandering values.
+    #[nserde(default_with = "default_wander")]
+    pub wander: Wander,
+    /// Clue kind weights.
+    #[nserde(default_with = "default_clues")]
+    pub clues: ClueWeights,
+}
+
+fn default_level_time() -> Curve {
+    Curve(vec![(0.0, 10.0)])
+}
+
+fn default_targets() -> Curve {
+    Curve(vec![(0.0, 1.0)])
+}
+
+/// Two targets at first, three from level 20
+fn default_contract_targets() -> Curve {
+    Curve(vec![(0.0, 2.0), (19.0, 2.0), (20.0, 3.0)])
+}
+
+/// Spawn 3 characters at first and add 1 for every 5 levels (max of 10)
+fn default_crowd_size() -> Curve {
+    Curve(vec![(0.0, 3.0), (35.0, 10.0)])
+}
+
+/// Starts at 1.0s and slowly decreases to 0.1s as the player progresses
+fn default_first_spawn_delay() -> Curve {
+    Curve(vec![(0.0, 1.0), (18.0, 0.1)])
+}
+
+fn default_easy() -> Preset {
+    Preset {
+        level_time: 1.5,
+        crowd_growth: 0.5,
+        hinted_traits: 4,
+        spawn_delay: 1.0,
+        score: 0.5,
+    }
+}
+
+fn default_normal() -> Preset {
+    Preset {
+        level_time: 1.0,
+        crowd_growth: 1.0,
+        hinted_traits: 3,
+        spawn_delay: 1.0,
+        score: 1.0,
+    }
+}
+
+fn default_hard() -> Preset {
+    Preset {
+        level_time: 0.7,
+        crowd_growth: 1.5,
+        hinted_traits: 2,
+        spawn_delay: 0.5,
+        score: 2.0,
+    }
+}
+
+fn default_time_attack() -> TimeAttack {
+    TimeAttack {
+        clock: 60.0,
+        kill_bonus: 2.0,
+        wrong_kill_penalty: 5.0,
+    }
+}
+
+/// Walks slowly at first and speeds up to 90 pixels per second
+fn default_wander() -> Wander {
+    Wander {
+        walk_speed: Curve(vec![(0.0, 30.0), (30.0, 90.0)]),
+        idle_min: 0.5,
+        idle_max: 3.0,
+        separation: 80.0,
+    }
+}
+
+/// Mostly trait clues, with the others mixed in
+fn default_clues() -> ClueWeights {
+    ClueWeights {
+        has: 4.0,
+        not: 1.0,
+        next_to: 0.5,
+        extreme: 0.5,
+        half: 0.5,
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            score_base: 100.0,
+            click_offset: 20.0,
+            spawn_delay: 0.2,
+            level_time: default_level_time(),
+            targets: default_targets(),
+            contract_targets: default_contract_targets(),
+            crowd_size: default_crowd_size(),
+            first_spawn_delay: default_first_spawn_delay(),
+            easy: default_easy(),
+            normal: default_normal(),
+            hard: default_hard(),
+            time_attack: default_time_attack(),
+            wander: default_wander(),
+            clues: default_clues(),
+        }
+    }
+}
+
+impl Tuning {
+    /// Loads the tuning file from the assets folder.
+    ///
+    /// Prints the errors and falls back to the defaults if the file is missing or invalid.
+    pub async fn load() -> Tuning {
+        let tuning = match load_string(TUNING_PATH).await {
+            Ok(ron) => Tuning::from_ron(&ron),
+            Err(e) => Err(e.to_string()),
+        };
+        tuning.unwrap_or_else(|e| {
+            eprintln!("Invalid {}, using the default tuning:\n{}", TUNING_PATH, e);
+            Tuning::default()
+        })
+    }
+
+    /// Returns the preset of the given difficulty.
+    pub fn preset(&self, difficulty: Difficulty) -> &Preset {
+        match difficulty {
+            Difficulty::Easy => &self.easy,
+            Difficulty::Normal => &self.normal,
+            Difficulty::Hard => &self.hard,
+        }
+    }
+
+    /// Returns the crowd size at the given level, with the preset's crowd growth applied.
+    pub fn crowd_size_at(&self, level: f32, preset: &Preset) -> usize {
+        let first = self.crowd_size.at(0.0);
+        let size = first + (self.crowd_size.at(level) - first) * preset.crowd_growth;
+        size.min(MAX_CROWD_SIZE) as usize
+    }
+
+    /// Returns the number of targets at the given level of the given mode.
+    pub fn targets_at(&self, level: f32, mode: GameMode) -> usize {
+        let targets = match mode {
+            GameMode::Contracts => &self.contract_targets,
+            _ => &self.targets,
+        };
+        targets.at(level).min(MAX_TARGETS) as usize
+    }
+
+    /// Returns a hash of the tuning values, to tell if a replay was recorded with the same.
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a, the standard library's hashers aren't stable across versions
+        format!("{:?}", self)
+            .bytes()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Parses and validates tuning values from RON.
+    pub fn from_ron(ron: &str) -> Result<Tuning, String> {
+        let tuning = Tuning::deserialize_ron(ron).map_err(|e| e.to_string())?;
+        tuning.validate()?;
+        Ok(tuning)
+    }
+
+    /// Checks that all values are in range. Returns every problem found, one per line.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.score_base < 0.0 {
+            errors.push("score_base: must not be negative".to_string());
+        }
+        if !(0.0..super::CHAR_HEIGHT).contains(&self.click_offset) {
+            errors.push(format!(
+                "click_offset: must be between 0 and {}",
+                super::CHAR_HEIGHT
+            ));
+        }
+        if self.spawn_delay < 0.0 {
+            errors.push("spawn_delay: must not be negative".to_string());
+        }
+        self.level_time
+            .validate("level_time", 0.1, f32::MAX, &mut errors);
+        self.targets
+            .validate("targets", 1.0, MAX_TARGETS, &mut errors);
+        self.contract_targets
+            .validate("contract_targets", 1.0, MAX_TARGETS, &mut errors);
+        self.crowd_size
+            .validate("crowd_size", 1.0, MAX_CROWD_SIZE, &mut errors);
+        self.first_spawn_delay
+            .validate("first_spawn_delay", 0.0, f32::MAX, &mut errors);
+        self.easy.validate("easy", &mut errors);
+        self.normal.validate("normal", &mut errors);
+        self.hard.validate("hard", &mut errors);
+        if self.time_attack.clock <= 0.0 {
+            errors.push("time_attack: clock must be positive".to_string());
+        }
+        if self.time_attack.kill_bonus < 0.0 || self.time_attack.wrong_kill_penalty < 0.0 {
+            errors.push(
+                "time_attack: kill_bonus and wrong_kill_penalty must not be negative".to_string(),
+            );
+        }
+        self.wander
+            .walk_speed
+            .validate("wander.walk_speed", 0.0, f32::MAX, &mut errors);
+        if self.wander.idle_min < 0.0 || self.wander.idle_max <= self.wander.idle_min {
+            errors.push("wander: idle_max must be greater than idle_min (>= 0)".to_string());
+        }
+        if self.wander.separation < 0.0 {
+            errors.push("wander: separation must not be negative".to_string());
+        }
+        let clues = &self.clues;
+        if [
+            clues.has,
+            clues.not,
+            clues.next_to,
+            clues.extreme,
+            clues.half,
+        ]
+        .iter()
+        .any(|&weight| weight < 0.0)
+        {
+            errors.push("clues: weights must not be negative".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_interpolate_and_clamp() {
+        let curve = Curve(vec![(0.0, 3.0), (10.0, 13.0), (20.0, 13.0)]);
+        assert_eq!(curve.at(-5.0), 3.0);
+        assert_eq!(curve.at(0.0), 3.0);
+        assert_eq!(curve.at(4.0), 7.0);
+        assert_eq!(curve.at(15.0), 13.0);
+        assert_eq!(curve.at(100.0), 13.0);
+        assert_eq!(Curve(vec![(5.0, 1.0)]).at(0.0), 1.0);
+    }
+
+    #[test]
+    fn the_default_and_shipped_tunings_are_valid() {
+        assert_eq!(Tuning::default().validate(), Ok(()));
+        let ron = std::fs::read_to_string(format!("assets/{}", TUNING_PATH)).unwrap();
+        assert_eq!(Tuning::from_ron(&ron), Ok(Tuning::default()));
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let tuning = Tuning {
+            score_base: -1.0,
+            targets: Curve(vec![(0.0, 1.0), (0.0, 4.0)]),
+            crowd_size: Curve(Vec::new()),
+            hard: Preset {
+                level_time: 0.0,
+                spawn_delay: -0.5,
+                ..default_hard()
+            },
+            ..Tuning::default()
+        };
+        let errors = tuning.validate().unwrap_err();
+        let errors: Vec<&str> = errors.lines().collect();
+        assert_eq!(
+            errors,
+            [
+                "score_base: must not be negative",
+                "targets: levels must be increasing",
+                "targets: value 4 at level 0 is not between 1 and 3",
+                "crowd_size: needs at least one point",
+                "hard: level_time must be positive",
+                "hard: crowd_growth and spawn_delay must not be negative",
+            ]
+        );
+    }
+}
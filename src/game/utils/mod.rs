@@ -3,5 +3,6 @@
 //! Small utilities for the game.
 
 pub mod colors;
+pub mod rng;
 pub mod sound;
 pub mod text;
@@ -0,0 +1,76 @@
+//! Seedable random number generator.
+//!
+//! A small PCG generator (the same one `quad-rand` uses) that lives in a value
+//! instead of a global, so a seed always reproduces the same sequence.
+
+use nanoserde::{DeJson, SerJson};
+
+const DEFAULT_INC: u64 = 1442695040888963407;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+/// Random number generator with cloneable and serializable state.
+#[derive(Clone, Debug, PartialEq, SerJson, DeJson)]
+pub struct Rng {
+    state: u64,
+}
+
+/// Types that can be generated in a range by `Rng::gen_range`.
+pub trait RandomRange: Sized {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(rng: &mut Rng, low: f32, high: f32) -> f32 {
+        let r = rng.rand() as f64 / u32::MAX as f64;
+        let value = low + (high - low) * r as f32;
+        // `r` can be 1 and the rounding can land on `high`, which is out of the range
+        if value >= high && high > low {
+            high.next_down()
+        } else {
+            value
+        }
+    }
+}
+
+impl RandomRange for usize {
+    fn gen_range(rng: &mut Rng, low: usize, high: usize) -> usize {
+        if high <= low {
+            return low;
+        }
+        let range = (high - low) as u64;
+        low + ((rng.rand() as u64 * range) >> 32) as usize
+    }
+}
+
+impl Rng {
+    /// Creates a generator seeded with `seed`.
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.rand();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.rand();
+        rng
+    }
+
+    /// Returns a pseudo-random number in the range of 0 to `u32::MAX`.
+    pub fn rand(&mut self) -> u32 {
+        let oldstate = self.state;
+        self.state = oldstate.wrapping_mul(MULTIPLIER).wrapping_add(DEFAULT_INC);
+        let xorshifted = (((oldstate >> 18) ^ oldstate) >> 27) as u32;
+        let rot = (oldstate >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Returns a random value in `low..high`.
+    pub fn gen_range<T: RandomRange>(&mut self, low: T, high: T) -> T {
+        T::gen_range(self, low, high)
+    }
+
+    /// Shuffles the slice in place.
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.gen_range(0, i + 1);
+            slice.swap(i, j);
+        }
+    }
+}
@@ -32,3 +32,28 @@ pub fn draw_blinking_text(
         draw_text_centered(text, x, y, font, font_size, color);
     }
 }
+
+/// Splits text into lines no wider than `max_width`, breaking between words. A word
+/// wider than `max_width` gets a line of its own.
+pub fn wrap_text(text: &str, font: Font, font_size: u16, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if line.is_empty()
+            || measure_text(&candidate, Some(font), font_size, 1.0).width <= max_width
+        {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -19,8 +19,35 @@ fn window_conf() -> Conf {
     }
 }
 
+/// Returns the replay file passed with `--replay <path>`, if any.
+#[cfg(not(target_arch = "wasm32"))]
+fn replay_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--replay" {
+            return args.next();
+        }
+    }
+    None
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut game = Game::init().await;
+    let mut game = match Game::init().await {
+        Ok(game) => game,
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("Failed to load asset {}", error);
+            }
+            show_asset_errors(&errors).await;
+            return;
+        }
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = replay_arg() {
+        game.play_replay_file(&path);
+    }
+
     game.run().await;
 }